@@ -1,5 +1,7 @@
 use bevy::prelude::*;
 
+use crate::{constants::OVERWORLD_TILESET_PATH, tower::map_gen::tileset::Tileset};
+
 pub struct AssetsPlugin;
 
 impl Plugin for AssetsPlugin {
@@ -12,6 +14,10 @@ pub struct TextureHandles {
     pub atlas: Handle<TextureAtlas>,
 }
 
+pub struct TilesetHandles {
+    pub overworld: Handle<Tileset>,
+}
+
 fn load_assets_system(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
@@ -21,4 +27,6 @@ fn load_assets_system(
     let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(8., 8.), 16, 10);
     let atlas = texture_atlases.add(texture_atlas);
     commands.insert_resource(TextureHandles { atlas });
+    let overworld = asset_server.load(OVERWORLD_TILESET_PATH);
+    commands.insert_resource(TilesetHandles { overworld });
 }
@@ -29,7 +29,10 @@ fn update_camera_system(
     player_query: Query<&Position, With<Player>>,
     mut camera_query: Query<&mut Transform, With<Camera2d>>,
 ) {
-    let pos = player_query.single();
+    let pos = match player_query.get_single() {
+        Ok(pos) => pos,
+        Err(_) => return,
+    };
     let mut tf = camera_query.single_mut();
     tf.translation.x = (pos.x * TILE_WIDTH) as f32;
     tf.translation.y = (pos.y * TILE_HEIGHT) as f32;
@@ -0,0 +1,159 @@
+use bevy::{app::AppExit, prelude::*};
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constants::SAVE_FILE_PATH,
+    player::Player,
+    position::Position,
+    save,
+    tower::{
+        tower::{Location, Tower},
+        OnMap,
+    },
+};
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AttackEvent>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                attack_system.label(CombatSystem::Attack),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                death_system
+                    .label(CombatSystem::Death)
+                    .after(CombatSystem::Attack),
+            );
+    }
+}
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CombatSystem {
+    Attack,
+    Death,
+}
+
+#[derive(Debug, Component, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Health {
+    pub current: i32,
+    pub max: i32,
+}
+
+impl Health {
+    pub fn new(max: i32) -> Self {
+        Health { current: max, max }
+    }
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0
+    }
+}
+
+#[derive(Debug, Component, Clone, Copy)]
+pub struct Attack(pub i32);
+
+#[derive(Debug, Component, Clone, Copy)]
+pub struct Defense(pub i32);
+
+pub struct AttackEvent {
+    pub attacker: Entity,
+    pub target: Entity,
+}
+
+pub fn roll_damage(rng: &mut impl Rng, attack: i32, defense: i32) -> i32 {
+    let attack = attack.max(0);
+    (rng.gen_range(attack / 2..=attack) - defense).max(0)
+}
+
+fn name_of(name: Option<&Name>) -> &str {
+    name.map_or("something", |name| name.as_str())
+}
+
+fn attack_system(
+    mut attack_events: EventReader<AttackEvent>,
+    attacker_query: Query<(&Attack, Option<&Name>)>,
+    mut target_query: Query<(&mut Health, Option<&Defense>, Option<&Name>)>,
+) {
+    let mut rng = thread_rng();
+    for event in attack_events.iter() {
+        let (attack, attacker_name) = match attacker_query.get(event.attacker) {
+            Ok(attacker) => attacker,
+            Err(_) => continue,
+        };
+        let (mut health, defense, target_name) = match target_query.get_mut(event.target) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+        if health.is_dead() {
+            continue;
+        }
+        let damage = roll_damage(&mut rng, attack.0, defense.map_or(0, |defense| defense.0));
+        health.current -= damage;
+        info!(
+            "{} hits {} for {} ({}/{})",
+            name_of(attacker_name),
+            name_of(target_name),
+            damage,
+            health.current.max(0),
+            health.max
+        );
+    }
+}
+
+type DeathQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Health,
+        &'static Position,
+        &'static OnMap,
+        Option<&'static Name>,
+        Option<&'static Player>,
+    ),
+    Changed<Health>,
+>;
+
+fn death_system(
+    mut commands: Commands,
+    query: DeathQuery,
+    mut tower: ResMut<Tower>,
+    mut exit_events: EventWriter<AppExit>,
+) {
+    for (entity, health, pos, on_map, name, player) in query.iter() {
+        if !health.is_dead() {
+            continue;
+        }
+        tower.remove_entity(entity, &Location::new(on_map.0, *pos));
+        commands.entity(entity).despawn();
+        if player.is_some() {
+            info!("you died");
+            if let Err(e) = save::remove(SAVE_FILE_PATH) {
+                error!("could not remove save: {}", e);
+            }
+            exit_events.send(AppExit);
+        } else {
+            info!("{} dies", name_of(name));
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn test_roll_damage() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let damage = roll_damage(&mut rng, 6, 1);
+            assert!((2..=5).contains(&damage));
+        }
+        assert_eq!(roll_damage(&mut rng, 2, 10), 0);
+        assert_eq!(roll_damage(&mut rng, -3, 0), 0);
+    }
+}
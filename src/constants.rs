@@ -1,6 +1,7 @@
 use bevy::prelude::*;
 
 // Settings
+pub const DEFAULT_WINDOW_TITLE: &str = "The Tower";
 pub const DEFAULT_BACKGROUND_COLOR: ClearColor = ClearColor(Color::BLACK);
 pub const DEFAULT_WINDOW_WIDTH: f32 = 1280.;
 pub const DEFAULT_WINDOW_HEIGHT: f32 = 720.;
@@ -13,6 +14,8 @@ pub const CAMERA_SCALE: f32 = 0.25;
 pub const TILE_WIDTH: i32 = 8;
 pub const TILE_HEIGHT: i32 = 8;
 pub const SPRITE_SCALE: Vec3 = Vec3::new(1., 1., 1.);
+pub const BUMP_OFFSET: f32 = 2.;
+pub const BUMP_SECONDS: f32 = 0.1;
 pub const PLAYER_SPRITE_INDEX: usize = 4;
 // pub const FLOOR_SPRITE_INDEX: usize = 97;
 pub const FLOOR_SPRITE_INDEX: usize = 17;
@@ -20,6 +23,50 @@ pub const FLOOR_SPRITE_INDEX: usize = 17;
 pub const DIRT_SPRITE_INDEX: usize = 68;
 pub const GRASS_SPRITE_INDEX: usize = 69;
 pub const WATER_SPRITE_INDEX: usize = 140;
+pub const WALL_SPRITE_INDEX: usize = 65;
+pub const DOOR_SPRITE_INDEX: usize = 36;
+pub const STAIRS_UP_SPRITE_INDEX: usize = 53;
+pub const STAIRS_DOWN_SPRITE_INDEX: usize = 52;
+
+pub const SEED_ENV_VAR: &str = "TOWER_SEED";
+pub const ANIMATE_WFC_ENV_VAR: &str = "TOWER_ANIMATE_WFC";
+pub const WFC_MAX_ATTEMPTS: usize = 10;
+pub const WFC_MAX_UNWALKABLE_RATIO: f32 = 0.3;
+pub const OVERWORLD_TILESET_PATH: &str = "tilesets/overworld.tileset.ron";
+pub const OVERWORLD_BORDER_TILE: &str = "water";
+pub const OVERWORLD_ENTRANCE_TILE: &str = "dirt";
+pub const RUINS_SAMPLE: &str = include_str!("../assets/samples/ruins.sample");
+pub const MARSH_SAMPLE: &str = include_str!("../assets/samples/marsh.sample");
+pub const OVERWORLD_SIZE: (usize, usize) = (20, 20);
+pub const BSP_LEVEL_SIZE: (usize, usize) = (30, 20);
+pub const CAVE_LEVEL_SIZE: (usize, usize) = (40, 30);
+pub const DRUNKARD_LEVEL_SIZE: (usize, usize) = (50, 40);
+pub const TUNNELER_LEVEL_SIZE: (usize, usize) = (60, 40);
+pub const RUINS_LEVEL_SIZE: (usize, usize) = (30, 20);
+pub const MARSH_LEVEL_SIZE: (usize, usize) = (40, 30);
+pub const LEVEL_GENERATION_ATTEMPTS: usize = 5;
+
+// Turn
+pub const ACTION_COST: i32 = 100;
+pub const PLAYER_SPEED: i32 = 100;
+
+// Combat
+pub const PLAYER_HEALTH: i32 = 20;
+pub const PLAYER_ATTACK: i32 = 5;
+pub const PLAYER_DEFENSE: i32 = 1;
+
+// Hostiles
+pub const HOSTILES_PER_MAP: usize = 3;
+pub const HOSTILE_FLEE_HEALTH: f32 = 0.25;
+pub const HOSTILE_FLEE_FACTOR: f32 = 1.2;
+pub const HOSTILE_REGROUP_WEIGHT: f32 = 0.5;
+pub const GOBLIN_SPRITE_INDEX: usize = 26;
+pub const SPIDER_SPRITE_INDEX: usize = 23;
+pub const SKELETON_SPRITE_INDEX: usize = 25;
+
+// Save
+pub const SAVE_FILE_PATH: &str = "save.ron";
+pub const SAVE_VERSION: u32 = 6;
 
 // Player
 pub const SPRITE_COLOR_EXPLORED_VISIBLE: Color = Color::WHITE;
@@ -0,0 +1,310 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::{
+    assets::TextureHandles,
+    combat::{Attack, AttackEvent, Defense, Health},
+    constants::{
+        ACTION_COST, GOBLIN_SPRITE_INDEX, HOSTILES_PER_MAP, HOSTILE_FLEE_FACTOR,
+        HOSTILE_FLEE_HEALTH, HOSTILE_REGROUP_WEIGHT, SKELETON_SPRITE_INDEX, SPIDER_SPRITE_INDEX,
+        SPRITE_SCALE,
+    },
+    player::Player,
+    position::{Position, DIRECTIONS},
+    save::{SavedEntity, SavedKind},
+    tower::{
+        grid::Grid,
+        legend::Spawn,
+        run_if_playing,
+        tower::{Location, MapId, Tower},
+        BlocksMovement, CurrentMap, OnMap,
+    },
+    turn::{Actor, TurnStage, TurnSystem},
+};
+
+pub struct HostilesPlugin;
+
+impl Plugin for HostilesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PopulatedMaps>()
+            .add_system(spawn_hostiles_system.with_run_criteria(run_if_playing))
+            .add_system_to_stage(
+                TurnStage,
+                hostile_ai_system
+                    .label(TurnSystem::Ai)
+                    .after(TurnSystem::Energy)
+                    .before(TurnSystem::End),
+            );
+    }
+}
+
+#[derive(Component)]
+pub struct Hostile {
+    pub glyph: char,
+}
+
+#[derive(Debug, Default)]
+pub struct PopulatedMaps(pub HashSet<MapId>);
+
+pub struct HostileKind {
+    pub glyph: char,
+    pub name: &'static str,
+    pub sprite_index: usize,
+    pub speed: i32,
+    pub health: i32,
+    pub attack: i32,
+    pub defense: i32,
+}
+
+const HOSTILE_KINDS: [HostileKind; 3] = [
+    HostileKind {
+        glyph: 'g',
+        name: "goblin",
+        sprite_index: GOBLIN_SPRITE_INDEX,
+        speed: 100,
+        health: 8,
+        attack: 3,
+        defense: 0,
+    },
+    HostileKind {
+        glyph: 's',
+        name: "spider",
+        sprite_index: SPIDER_SPRITE_INDEX,
+        speed: 150,
+        health: 4,
+        attack: 2,
+        defense: 0,
+    },
+    HostileKind {
+        glyph: 'z',
+        name: "skeleton",
+        sprite_index: SKELETON_SPRITE_INDEX,
+        speed: 50,
+        health: 12,
+        attack: 4,
+        defense: 1,
+    },
+];
+
+impl HostileKind {
+    pub fn from_glyph(glyph: char) -> Option<&'static HostileKind> {
+        HOSTILE_KINDS.iter().find(|kind| kind.glyph == glyph)
+    }
+}
+
+fn spawn_hostile(
+    commands: &mut Commands,
+    texture_handles: &TextureHandles,
+    tower: &mut Tower,
+    kind: &HostileKind,
+    location: Location,
+    health: Health,
+) {
+    let entity = commands
+        .spawn_bundle(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                index: kind.sprite_index,
+                ..default()
+            },
+            texture_atlas: texture_handles.atlas.clone(),
+            transform: Transform {
+                translation: Vec3::new(0., 0., 5.),
+                scale: SPRITE_SCALE,
+                ..default()
+            },
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(Name::new(kind.name))
+        .insert(location.pos)
+        .insert(OnMap(location.map))
+        .insert(BlocksMovement)
+        .insert(Actor::new(kind.speed))
+        .insert(health)
+        .insert(Attack(kind.attack))
+        .insert(Defense(kind.defense))
+        .insert(Hostile { glyph: kind.glyph })
+        .id();
+    tower.insert_entity(entity, &location);
+}
+
+pub fn restore_hostiles(
+    commands: &mut Commands,
+    texture_handles: &TextureHandles,
+    tower: &mut Tower,
+    entities: &[SavedEntity],
+) {
+    for entity in entities {
+        let kind = match entity.kind {
+            SavedKind::Hostile(glyph) => HostileKind::from_glyph(glyph),
+            SavedKind::Player => None,
+        };
+        if let Some(kind) = kind {
+            spawn_hostile(
+                commands,
+                texture_handles,
+                tower,
+                kind,
+                entity.location,
+                entity.health,
+            );
+        }
+    }
+}
+
+fn can_enter(
+    tower: &Tower,
+    map: MapId,
+    pos: &Position,
+    blocker_query: &Query<Entity, With<BlocksMovement>>,
+) -> bool {
+    match tower.get_tile(map, pos) {
+        Some(tile) if tile.walkable() => !tile.entities.iter().any(|&e| blocker_query.contains(e)),
+        _ => false,
+    }
+}
+
+fn spawn_hostiles_system(
+    mut commands: Commands,
+    texture_handles: Res<TextureHandles>,
+    player_query: Query<&Position, With<Player>>,
+    blocker_query: Query<Entity, With<BlocksMovement>>,
+    current_map: Res<CurrentMap>,
+    mut tower: ResMut<Tower>,
+    mut populated: ResMut<PopulatedMaps>,
+) {
+    let map = current_map.map;
+    if !populated.0.insert(map) {
+        return;
+    }
+    let mut spawns: Vec<(Position, &HostileKind)> = tower
+        .get_spawns(map)
+        .into_iter()
+        .filter_map(|(pos, spawn)| match spawn {
+            Spawn::Hostile(glyph) => HostileKind::from_glyph(glyph).map(|kind| (pos, kind)),
+            Spawn::Player => None,
+        })
+        .collect();
+    if spawns.is_empty() {
+        let (width, height) = match tower.get_dimensions(map) {
+            Some(dimensions) => dimensions,
+            None => return,
+        };
+        let seen = match player_query.get_single() {
+            Ok(player_pos) => tower.get_visible(map, player_pos),
+            Err(_) => HashSet::new(),
+        };
+        let candidates: Vec<Position> = (0..height as i32)
+            .flat_map(|y| (0..width as i32).map(move |x| Position::new(x, y)))
+            .filter(|pos| !seen.contains(pos) && can_enter(&tower, map, pos, &blocker_query))
+            .collect();
+        let mut rng = thread_rng();
+        spawns = candidates
+            .choose_multiple(&mut rng, HOSTILES_PER_MAP)
+            .map(|&pos| (pos, HOSTILE_KINDS.choose(&mut rng).unwrap()))
+            .collect();
+    }
+    for (pos, kind) in spawns {
+        spawn_hostile(
+            &mut commands,
+            &texture_handles,
+            &mut tower,
+            kind,
+            Location::new(map, pos),
+            Health::new(kind.health),
+        );
+    }
+}
+
+type HostileQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Position,
+        &'static OnMap,
+        &'static mut Actor,
+        &'static Health,
+    ),
+    (With<Hostile>, Without<Player>),
+>;
+
+fn hostile_ai_system(
+    mut hostile_query: HostileQuery,
+    player_query: Query<(Entity, &Position, &OnMap), With<Player>>,
+    blocker_query: Query<Entity, With<BlocksMovement>>,
+    current_map: Res<CurrentMap>,
+    mut tower: ResMut<Tower>,
+    mut attack_events: EventWriter<AttackEvent>,
+) {
+    let player = player_query.get_single().ok();
+    let hostiles: Vec<(Entity, Position, MapId)> = hostile_query
+        .iter()
+        .map(|(entity, pos, on_map, _, _)| (entity, *pos, on_map.0))
+        .collect();
+    let mut rng = thread_rng();
+    for (entity, mut pos, on_map, mut actor, health) in hostile_query.iter_mut() {
+        if on_map.0 != current_map.map || !actor.ready {
+            continue;
+        }
+        actor.spend(ACTION_COST);
+        let target = match player {
+            Some((player, player_pos, player_map))
+                if player_map.0 == on_map.0
+                    && tower.get_visible(on_map.0, &pos).contains(player_pos) =>
+            {
+                Some((player, *player_pos))
+            }
+            _ => None,
+        };
+        let fleeing = (health.current as f32) <= health.max as f32 * HOSTILE_FLEE_HEALTH;
+        if let Some((player, player_pos)) = target {
+            if !fleeing && pos.distance2(&player_pos) <= 1. {
+                attack_events.send(AttackEvent {
+                    attacker: entity,
+                    target: player,
+                });
+                continue;
+            }
+        }
+        let next = match target {
+            Some((_, target)) if fleeing => {
+                let is_blocker = |e| e != entity && blocker_query.contains(e);
+                let allies: Vec<Position> = hostiles
+                    .iter()
+                    .filter(|&&(other, _, map)| other != entity && map == on_map.0)
+                    .map(|&(_, ally, _)| ally)
+                    .collect();
+                let flee = tower.flee_map(on_map.0, &[target], HOSTILE_FLEE_FACTOR, is_blocker);
+                let regroup = tower.distance_map(on_map.0, &allies, is_blocker);
+                let desire = match (flee, regroup) {
+                    (Some(flee), Some(regroup))
+                        if regroup.get(&pos).is_some_and(|d| d.is_finite()) =>
+                    {
+                        Grid::combine(&[(&flee, 1.), (&regroup, HOSTILE_REGROUP_WEIGHT)])
+                    }
+                    (flee, _) => flee,
+                };
+                desire.and_then(|map| map.downhill(&pos))
+            }
+            Some((_, target)) => tower
+                .find_path(on_map.0, *pos, target, |e| blocker_query.contains(e))
+                .and_then(|path| path.first().copied()),
+            None => DIRECTIONS
+                .iter()
+                .map(|&dir| *pos + dir)
+                .filter(|next| can_enter(&tower, on_map.0, next, &blocker_query))
+                .collect::<Vec<Position>>()
+                .choose(&mut rng)
+                .copied(),
+        };
+        if let Some(next) = next {
+            let from = Location::new(on_map.0, *pos);
+            let to = Location::new(on_map.0, next);
+            tower.move_entity(entity, &from, &to);
+            *pos = next;
+        }
+    }
+}
@@ -2,19 +2,26 @@ use bevy::prelude::*;
 
 use assets::AssetsPlugin;
 use camera::CameraPlugin;
+use combat::CombatPlugin;
+use hostiles::HostilesPlugin;
 use player::PlayerPlugin;
 use position::PositionPlugin;
+use save::SavePlugin;
 use settings::SettingsPlugin;
 use tower::TowerPlugin;
+use turn::TurnPlugin;
 
 mod assets;
 mod camera;
+mod combat;
 mod constants;
-// mod hostiles;
+mod hostiles;
 mod player;
 mod position;
+mod save;
 mod settings;
 mod tower;
+mod turn;
 mod utils;
 
 fn main() {
@@ -24,7 +31,11 @@ fn main() {
         .add_plugin(CameraPlugin)
         .add_plugin(AssetsPlugin)
         .add_plugin(PlayerPlugin)
+        .add_plugin(HostilesPlugin)
         .add_plugin(PositionPlugin)
         .add_plugin(TowerPlugin)
+        .add_plugin(TurnPlugin)
+        .add_plugin(CombatPlugin)
+        .add_plugin(SavePlugin)
         .run();
 }
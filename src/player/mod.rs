@@ -1,9 +1,15 @@
 use bevy::prelude::*;
 
 use crate::{
-    constants::{SPRITE_COLOR_EXPLORED_SHROUDED, SPRITE_COLOR_EXPLORED_VISIBLE},
-    position::Position,
-    tower::{tower::Tower, Explorable},
+    combat::{AttackEvent, Health},
+    constants::{ACTION_COST, SPRITE_COLOR_EXPLORED_SHROUDED, SPRITE_COLOR_EXPLORED_VISIBLE},
+    position::{Bump, Position},
+    tower::{
+        tile::Stairs,
+        tower::{Location, Tower},
+        BlocksMovement, CurrentMap, Explorable, OnMap,
+    },
+    turn::{run_if_player_turn, Actor},
 };
 
 pub struct PlayerPlugin;
@@ -13,42 +19,124 @@ pub struct Player;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(input_system)
-            .add_system(update_visiblity_system)
-            .add_system(update_explorable_system);
+        app.add_system_set(
+            SystemSet::new()
+                .with_run_criteria(run_if_player_turn)
+                .with_system(input_system)
+                .with_system(stairs_system),
+        )
+        .add_system(update_visiblity_system)
+        .add_system(update_explorable_system);
     }
 }
 
 fn input_system(
-    mut query: Query<&mut Position, With<Player>>,
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Position, &OnMap, &mut Actor), With<Player>>,
+    blocker_query: Query<Entity, With<BlocksMovement>>,
+    health_query: Query<Entity, With<Health>>,
     keyboard_input: Res<Input<KeyCode>>,
+    mut tower: ResMut<Tower>,
+    mut attack_events: EventWriter<AttackEvent>,
 ) {
-    let mut pos = query
-        .get_single_mut()
-        .expect("Error: could not find player");
-    if keyboard_input.just_pressed(KeyCode::H) {
-        pos.x -= 1;
-    }
-    if keyboard_input.just_pressed(KeyCode::L) {
-        pos.x += 1;
+    let dir = if keyboard_input.just_pressed(KeyCode::H) {
+        Position::LEFT
+    } else if keyboard_input.just_pressed(KeyCode::L) {
+        Position::RIGHT
+    } else if keyboard_input.just_pressed(KeyCode::J) {
+        Position::DOWN
+    } else if keyboard_input.just_pressed(KeyCode::K) {
+        Position::UP
+    } else {
+        return;
+    };
+    let (entity, mut pos, on_map, mut actor) = match query.get_single_mut() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let from = Location::new(on_map.0, *pos);
+    let to = Location::new(on_map.0, *pos + dir);
+    let target = tower.get_tile(to.map, &to.pos).and_then(|tile| {
+        tile.entities
+            .iter()
+            .copied()
+            .find(|&e| e != entity && health_query.contains(e))
+    });
+    match (tower.get_tile(to.map, &to.pos), target) {
+        (None, _) => {
+            info!("bumped into the edge of the map");
+        }
+        (Some(tile), _) if !tile.walkable() => {
+            info!("bumped into {}", tile.terrain.name());
+        }
+        (Some(_), Some(target)) => {
+            attack_events.send(AttackEvent {
+                attacker: entity,
+                target,
+            });
+            actor.spend(ACTION_COST);
+        }
+        (Some(tile), None)
+            if tile
+                .entities
+                .iter()
+                .any(|&e| e != entity && blocker_query.contains(e)) =>
+        {
+            info!("bumped into something");
+        }
+        (Some(_), None) => {
+            tower.move_entity(entity, &from, &to);
+            *pos = to.pos;
+            actor.spend(ACTION_COST);
+            return;
+        }
     }
-    if keyboard_input.just_pressed(KeyCode::J) {
-        pos.y -= 1;
+    commands.entity(entity).insert(Bump::new(dir));
+}
+
+fn stairs_system(
+    mut query: Query<(Entity, &mut Position, &mut OnMap, &mut Actor), With<Player>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut tower: ResMut<Tower>,
+) {
+    let stairs = if keyboard_input.just_pressed(KeyCode::Comma) {
+        Stairs::Up
+    } else if keyboard_input.just_pressed(KeyCode::Period) {
+        Stairs::Down
+    } else {
+        return;
+    };
+    let (entity, mut pos, mut on_map, mut actor) = match query.get_single_mut() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let from = Location::new(on_map.0, *pos);
+    match tower.get_tile(from.map, &from.pos) {
+        Some(tile) if tile.stairs == Some(stairs) => {}
+        _ => return,
     }
-    if keyboard_input.just_pressed(KeyCode::K) {
-        pos.y += 1;
+    if let Some(to) = tower.get_stairs_destination(&from) {
+        tower.move_entity(entity, &from, &to);
+        *pos = to.pos;
+        on_map.0 = to.map;
+        actor.spend(ACTION_COST);
     }
 }
 
 fn update_visiblity_system(
     player_query: Query<&Position, With<Player>>,
-    mut ent_query: Query<(&mut Visibility, &Position), (Without<Player>, Without<Explorable>)>,
+    mut ent_query: Query<
+        (&mut Visibility, &Position, &OnMap),
+        (Without<Player>, Without<Explorable>),
+    >,
+    current_map: Res<CurrentMap>,
 ) {
-    let player_pos = player_query
-        .get_single()
-        .expect("Error: could not find player");
-    for (mut vis, pos) in ent_query.iter_mut() {
-        match player_pos.distance(pos) < 5. {
+    let player_pos = match player_query.get_single() {
+        Ok(pos) => pos,
+        Err(_) => return,
+    };
+    for (mut vis, pos, on_map) in ent_query.iter_mut() {
+        match on_map.0 == current_map.map && player_pos.distance(pos) < 5. {
             true => vis.is_visible = true,
             false => vis.is_visible = false,
         };
@@ -57,12 +145,23 @@ fn update_visiblity_system(
 
 fn update_explorable_system(
     player_query: Query<&Position, With<Player>>,
-    mut ent_query: Query<(&mut Visibility, &mut TextureAtlasSprite, &Position), With<Explorable>>,
+    mut ent_query: Query<
+        (&mut Visibility, &mut TextureAtlasSprite, &Position, &OnMap),
+        With<Explorable>,
+    >,
     tower: Res<Tower>,
+    current_map: Res<CurrentMap>,
 ) {
-    let player_pos = player_query.single();
-    let visible_pos = tower.get_visible("level1", "map1", &player_pos);
-    for (mut vis, mut sprite, pos) in ent_query.iter_mut() {
+    let player_pos = match player_query.get_single() {
+        Ok(pos) => pos,
+        Err(_) => return,
+    };
+    let visible_pos = tower.get_visible(current_map.map, &player_pos);
+    for (mut vis, mut sprite, pos, on_map) in ent_query.iter_mut() {
+        if on_map.0 != current_map.map {
+            vis.is_visible = false;
+            continue;
+        }
         if visible_pos.contains(pos) {
             vis.is_visible = true;
             sprite.color = SPRITE_COLOR_EXPLORED_VISIBLE;
@@ -1,14 +1,16 @@
 use std::ops::{Add, Mul, Sub};
 
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::constants::{TILE_HEIGHT, TILE_WIDTH};
+use crate::constants::{BUMP_OFFSET, BUMP_SECONDS, TILE_HEIGHT, TILE_WIDTH};
 
 pub struct PositionPlugin;
 
 impl Plugin for PositionPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(update_position_system);
+        app.add_system(update_position_system)
+            .add_system(update_bump_system);
     }
 }
 pub const DIRECTIONS: &'static [Position] = &[
@@ -18,7 +20,7 @@ pub const DIRECTIONS: &'static [Position] = &[
     Position::RIGHT,
 ];
 
-#[derive(Debug, Component, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, Component, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
@@ -62,6 +64,21 @@ impl Position {
     }
 }
 
+#[derive(Component)]
+pub struct Bump {
+    pub dir: Position,
+    timer: Timer,
+}
+
+impl Bump {
+    pub fn new(dir: Position) -> Self {
+        Bump {
+            dir,
+            timer: Timer::from_seconds(BUMP_SECONDS, false),
+        }
+    }
+}
+
 struct Neighbors {
     origin: Position,
     index: usize,
@@ -117,9 +134,26 @@ impl Mul<i32> for Position {
     }
 }
 
-fn update_position_system(mut query: Query<(&mut Transform, &Position)>) {
-    for (mut tf, pos) in query.iter_mut() {
+fn update_position_system(mut query: Query<(&mut Transform, &Position, Option<&Bump>)>) {
+    for (mut tf, pos, bump) in query.iter_mut() {
         tf.translation.x = (pos.x * TILE_WIDTH) as f32;
         tf.translation.y = (pos.y * TILE_HEIGHT) as f32;
+        if let Some(bump) = bump {
+            tf.translation.x += bump.dir.x as f32 * BUMP_OFFSET;
+            tf.translation.y += bump.dir.y as f32 * BUMP_OFFSET;
+        }
+    }
+}
+
+fn update_bump_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Bump)>,
+) {
+    for (entity, mut bump) in query.iter_mut() {
+        bump.timer.tick(time.delta());
+        if bump.timer.finished() {
+            commands.entity(entity).remove::<Bump>();
+        }
     }
 }
@@ -0,0 +1,274 @@
+use std::{fmt, fs, io};
+
+use bevy::{app::AppExit, prelude::*, window::WindowCloseRequested};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::Health,
+    constants::{SAVE_FILE_PATH, SAVE_VERSION},
+    hostiles::{Hostile, PopulatedMaps},
+    player::Player,
+    position::Position,
+    tower::{
+        tower::{Location, MapId, Tower},
+        GameState, OnMap, Seed,
+    },
+};
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(save_on_exit_system);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SavedKind {
+    Player,
+    Hostile(char),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedEntity {
+    pub kind: SavedKind,
+    pub location: Location,
+    pub health: Health,
+}
+
+#[derive(Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    pub seed: u64,
+    pub tower: Tower,
+    pub entities: Vec<SavedEntity>,
+    pub populated: Vec<MapId>,
+}
+
+impl SaveGame {
+    pub fn player(&self) -> Option<SavedEntity> {
+        self.entities
+            .iter()
+            .find(|entity| entity.kind == SavedKind::Player)
+            .copied()
+    }
+}
+
+#[derive(Serialize)]
+struct SaveGameRef<'a> {
+    version: u32,
+    seed: u64,
+    tower: &'a Tower,
+    entities: &'a [SavedEntity],
+    populated: &'a [MapId],
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Serialize(ron::Error),
+    Deserialize(ron::error::SpannedError),
+    Version(u32),
+}
+
+impl SaveError {
+    pub fn is_missing(&self) -> bool {
+        matches!(self, SaveError::Io(e) if e.kind() == io::ErrorKind::NotFound)
+    }
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "io error: {}", e),
+            SaveError::Serialize(e) => write!(f, "could not serialize save: {}", e),
+            SaveError::Deserialize(e) => write!(f, "could not deserialize save: {}", e),
+            SaveError::Version(v) => write!(
+                f,
+                "save version {} does not match expected version {}",
+                v, SAVE_VERSION
+            ),
+        }
+    }
+}
+
+pub fn to_string(
+    seed: u64,
+    tower: &Tower,
+    entities: &[SavedEntity],
+    populated: &[MapId],
+) -> Result<String, SaveError> {
+    let save_game = SaveGameRef {
+        version: SAVE_VERSION,
+        seed,
+        tower,
+        entities,
+        populated,
+    };
+    ron::ser::to_string_pretty(&save_game, PrettyConfig::default()).map_err(SaveError::Serialize)
+}
+
+pub fn from_str(s: &str) -> Result<SaveGame, SaveError> {
+    let save_game: SaveGame = ron::from_str(s).map_err(SaveError::Deserialize)?;
+    if save_game.version != SAVE_VERSION {
+        return Err(SaveError::Version(save_game.version));
+    }
+    Ok(save_game)
+}
+
+pub fn save(
+    path: &str,
+    seed: u64,
+    tower: &Tower,
+    entities: &[SavedEntity],
+    populated: &[MapId],
+) -> Result<(), SaveError> {
+    let s = to_string(seed, tower, entities, populated)?;
+    fs::write(path, s).map_err(SaveError::Io)
+}
+
+pub fn load(path: &str) -> Result<SaveGame, SaveError> {
+    let s = fs::read_to_string(path).map_err(SaveError::Io)?;
+    from_str(&s)
+}
+
+pub fn remove(path: &str) -> Result<(), SaveError> {
+    match fs::remove_file(path).map_err(SaveError::Io) {
+        Err(e) if e.is_missing() => Ok(()),
+        result => result,
+    }
+}
+
+type SavedEntityQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Position,
+        &'static OnMap,
+        &'static Health,
+        Option<&'static Player>,
+        Option<&'static Hostile>,
+    ),
+>;
+
+fn save_on_exit_system(
+    mut close_events: EventReader<WindowCloseRequested>,
+    mut exit_events: EventReader<AppExit>,
+    tower: Res<Tower>,
+    seed: Res<Seed>,
+    game_state: Res<GameState>,
+    entity_query: SavedEntityQuery,
+    populated: Res<PopulatedMaps>,
+) {
+    let closing = close_events.iter().count() > 0;
+    let exiting = exit_events.iter().count() > 0;
+    if !closing && !exiting {
+        return;
+    }
+    if *game_state == GameState::Loading {
+        return;
+    }
+    let entities: Vec<SavedEntity> = entity_query
+        .iter()
+        .filter_map(|(pos, on_map, health, player, hostile)| {
+            let kind = match (player, hostile) {
+                (Some(_), _) => SavedKind::Player,
+                (None, Some(hostile)) => SavedKind::Hostile(hostile.glyph),
+                (None, None) => return None,
+            };
+            Some(SavedEntity {
+                kind,
+                location: Location::new(on_map.0, *pos),
+                health: *health,
+            })
+        })
+        .collect();
+    if !entities
+        .iter()
+        .any(|entity| entity.kind == SavedKind::Player)
+    {
+        return;
+    }
+    let populated: Vec<MapId> = populated.0.iter().copied().collect();
+    if let Err(e) = save(SAVE_FILE_PATH, seed.0, &tower, &entities, &populated) {
+        error!("could not save game: {}", e);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::tower::tile::Stairs;
+
+    #[test]
+    fn test_save_round_trip() {
+        let mut tower = Tower::new();
+        let level1 = tower.insert_level("level1");
+        let level2 = tower.insert_level("level2");
+        let map1 = tower.insert_empty_map(level1, "map1", 5, 5).unwrap();
+        let map2 = tower
+            .insert_map_from_str(
+                level2,
+                "map1",
+                3,
+                3,
+                "
+        |W|W|W|
+        |W| |W|
+        |W|W|W|
+                ",
+            )
+            .unwrap();
+        let lower = Location::new(map1, Position::new(4, 4));
+        let upper = Location::new(map2, Position::new(1, 1));
+        tower.insert_stairs(lower, upper);
+        let player = Location::new(map1, Position::new(2, 3));
+        let entities = vec![
+            SavedEntity {
+                kind: SavedKind::Hostile('g'),
+                location: Location::new(map2, Position::new(1, 1)),
+                health: Health { current: 3, max: 8 },
+            },
+            SavedEntity {
+                kind: SavedKind::Player,
+                location: player,
+                health: Health {
+                    current: 7,
+                    max: 20,
+                },
+            },
+        ];
+
+        let s = to_string(1234, &tower, &entities, &[map2]).unwrap();
+        let save_game = from_str(&s).unwrap();
+        assert_eq!(save_game.seed, 1234);
+        assert_eq!(save_game.player(), Some(entities[1]));
+        assert_eq!(save_game.entities, entities);
+        assert_eq!(save_game.populated, vec![map2]);
+        let mut loaded = save_game.tower;
+        assert_eq!(loaded.find_level("level2"), Some(level2));
+        assert_eq!(loaded.find_map(level2, "map1"), Some(map2));
+        assert_eq!(loaded.get_stairs_destination(&lower), Some(upper));
+        assert_eq!(
+            loaded.get_tile(map2, &upper.pos).unwrap().stairs,
+            Some(Stairs::Down)
+        );
+        assert!(!loaded.get_tile(map2, &Position::ZERO).unwrap().walkable());
+        let map3 = loaded.insert_empty_map(level1, "map2", 1, 1).unwrap();
+        assert_ne!(map3, map1);
+        assert_ne!(map3, map2);
+    }
+
+    #[test]
+    fn test_save_version_mismatch() {
+        let tower = Tower::new();
+        let s = to_string(0, &tower, &[], &[]).unwrap();
+        let s = s.replacen(
+            &format!("version: {}", SAVE_VERSION),
+            &format!("version: {}", SAVE_VERSION + 1),
+            1,
+        );
+        assert!(matches!(from_str(&s), Err(SaveError::Version(_))));
+    }
+}
@@ -1,6 +1,8 @@
 use bevy::{prelude::*, render::texture::ImageSettings};
 
-use crate::constants::{DEFAULT_BACKGROUND_COLOR, DEFAULT_WINDOW_HEIGHT, DEFAULT_WINDOW_WIDTH};
+use crate::constants::{
+    DEFAULT_BACKGROUND_COLOR, DEFAULT_WINDOW_HEIGHT, DEFAULT_WINDOW_TITLE, DEFAULT_WINDOW_WIDTH,
+};
 
 pub struct SettingsPlugin;
 
@@ -9,7 +11,7 @@ impl Plugin for SettingsPlugin {
         app.insert_resource(ImageSettings::default_nearest())
             .insert_resource(DEFAULT_BACKGROUND_COLOR)
             .insert_resource(WindowDescriptor {
-                title: "The Tower".to_string(),
+                title: DEFAULT_WINDOW_TITLE.to_string(),
                 width: DEFAULT_WINDOW_WIDTH,
                 height: DEFAULT_WINDOW_HEIGHT,
                 resizable: false,
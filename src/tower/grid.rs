@@ -1,5 +1,10 @@
-use crate::position::Position;
+use std::{cmp::Ordering, collections::BinaryHeap};
 
+use serde::{Deserialize, Serialize};
+
+use crate::position::{Position, DIRECTIONS};
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Grid<T> {
     pub width: usize,
     pub height: usize,
@@ -20,6 +25,17 @@ impl<T> Grid<T> {
         }
     }
 
+    pub fn filled(width: usize, height: usize, value: T) -> Self
+    where
+        T: Clone,
+    {
+        Grid {
+            width,
+            height,
+            cells: vec![value; width * height],
+        }
+    }
+
     pub fn get(&self, pos: &Position) -> Option<&T> {
         if !self.in_bounds(pos) {
             return None;
@@ -48,6 +64,35 @@ impl<T> Grid<T> {
         self.cells.iter_mut()
     }
 
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+
+    pub fn index_of(&self, pos: &Position) -> Option<usize> {
+        self.in_bounds(pos).then(|| self.index(pos))
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Position> {
+        let width = self.width as i32;
+        let height = self.height as i32;
+        (0..height).flat_map(move |y| (0..width).map(move |x| Position::new(x, y)))
+    }
+
+    pub fn dijkstra_map(&self, goals: &[Position], cost: impl Fn(&T) -> Option<f32>) -> Grid<f32> {
+        let mut map = Grid::filled(self.width, self.height, f32::INFINITY);
+        for goal in goals {
+            if let Some(value) = map.get_mut(goal) {
+                *value = 0.;
+            }
+        }
+        map.relax(self, cost);
+        map
+    }
+
     fn in_bounds(&self, pos: &Position) -> bool {
         pos.x >= 0 && pos.x < self.width as i32 && pos.y >= 0 && pos.y < self.height as i32
     }
@@ -56,3 +101,156 @@ impl<T> Grid<T> {
         pos.y as usize * self.width + pos.x as usize
     }
 }
+
+impl Grid<f32> {
+    pub fn relax<T>(&mut self, terrain: &Grid<T>, cost: impl Fn(&T) -> Option<f32>) {
+        let mut open: BinaryHeap<Node> = self
+            .positions()
+            .filter_map(|pos| {
+                let value = *self.get(&pos)?;
+                value.is_finite().then_some(Node { value, pos })
+            })
+            .collect();
+        while let Some(Node { value, pos }) = open.pop() {
+            if self.get(&pos).is_none_or(|&current| current < value) {
+                continue;
+            }
+            for dir in DIRECTIONS {
+                let next = pos + *dir;
+                let step = match terrain.get(&next).and_then(&cost) {
+                    Some(step) => step,
+                    None => continue,
+                };
+                let next_value = value + step;
+                if let Some(current) = self.get_mut(&next) {
+                    if next_value < *current {
+                        *current = next_value;
+                        open.push(Node {
+                            value: next_value,
+                            pos: next,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn combine(maps: &[(&Grid<f32>, f32)]) -> Option<Grid<f32>> {
+        let (first, _) = maps.first()?;
+        if maps
+            .iter()
+            .any(|(map, _)| map.width != first.width || map.height != first.height)
+        {
+            return None;
+        }
+        let mut combined = Grid::filled(first.width, first.height, 0.);
+        for (map, weight) in maps {
+            for (value, other) in combined.cells.iter_mut().zip(map.cells.iter()) {
+                if !other.is_finite() {
+                    *value = f32::INFINITY;
+                } else if value.is_finite() {
+                    *value += other * weight;
+                }
+            }
+        }
+        Some(combined)
+    }
+
+    pub fn scale(&mut self, factor: f32) {
+        for value in self.cells.iter_mut().filter(|value| value.is_finite()) {
+            *value *= factor;
+        }
+    }
+
+    pub fn flee<T>(
+        &self,
+        terrain: &Grid<T>,
+        factor: f32,
+        cost: impl Fn(&T) -> Option<f32>,
+    ) -> Grid<f32> {
+        let mut map = self.clone();
+        map.scale(-factor);
+        map.relax(terrain, cost);
+        map
+    }
+
+    pub fn downhill(&self, pos: &Position) -> Option<Position> {
+        let current = *self.get(pos)?;
+        DIRECTIONS
+            .iter()
+            .map(|dir| *pos + *dir)
+            .filter_map(|next| self.get(&next).map(|&value| (next, value)))
+            .filter(|&(_, value)| value < current)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(next, _)| next)
+    }
+}
+
+struct Node {
+    value: f32,
+    pos: Position,
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Node {}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.value.total_cmp(&self.value)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn open_cost(walkable: &bool) -> Option<f32> {
+        walkable.then_some(1.)
+    }
+
+    #[test]
+    fn test_dijkstra_map() {
+        let mut terrain: Grid<bool> = Grid::filled(5, 1, true);
+        *terrain.get_mut(&Position::new(3, 0)).unwrap() = false;
+        let map = terrain.dijkstra_map(&[Position::new(0, 0)], open_cost);
+        assert_eq!(map.get(&Position::new(2, 0)), Some(&2.));
+        assert_eq!(map.get(&Position::new(4, 0)), Some(&f32::INFINITY));
+        assert_eq!(
+            map.downhill(&Position::new(2, 0)),
+            Some(Position::new(1, 0))
+        );
+        assert_eq!(map.downhill(&Position::new(0, 0)), None);
+    }
+
+    #[test]
+    fn test_combine_and_flee() {
+        let terrain: Grid<bool> = Grid::filled(5, 1, true);
+        let left = terrain.dijkstra_map(&[Position::new(0, 0)], open_cost);
+        let right = terrain.dijkstra_map(&[Position::new(4, 0)], open_cost);
+        let combined = Grid::combine(&[(&left, 1.), (&right, 0.5)]).unwrap();
+        assert_eq!(combined.get(&Position::new(1, 0)), Some(&2.5));
+        assert_eq!(
+            combined.downhill(&Position::new(2, 0)),
+            Some(Position::new(1, 0))
+        );
+        assert!(Grid::combine(&[(&left, 1.), (&Grid::filled(1, 1, 0.), 1.)]).is_none());
+
+        let flee = left.flee(&terrain, 1.2, open_cost);
+        assert_eq!(
+            flee.downhill(&Position::new(2, 0)),
+            Some(Position::new(3, 0))
+        );
+        assert_eq!(flee.downhill(&Position::new(4, 0)), None);
+    }
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+use super::tile::{Stairs, Terrain};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Spawn {
+    Player,
+    Hostile(char),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Glyph {
+    Terrain(Terrain),
+    Stairs(Stairs),
+    Spawn(Spawn),
+}
+
+pub struct Legend {
+    glyphs: Vec<(char, Glyph)>,
+}
+
+impl Legend {
+    pub fn new() -> Self {
+        Legend { glyphs: Vec::new() }
+    }
+    pub fn insert(&mut self, c: char, glyph: Glyph) {
+        self.glyphs.retain(|&(other, _)| other != c);
+        self.glyphs.push((c, glyph));
+    }
+    pub fn glyph(&self, c: char) -> Option<Glyph> {
+        self.glyphs
+            .iter()
+            .find(|&&(other, _)| other == c)
+            .map(|&(_, glyph)| glyph)
+    }
+    pub fn char(&self, glyph: Glyph) -> Option<char> {
+        self.glyphs
+            .iter()
+            .find(|&&(_, other)| other == glyph)
+            .map(|&(c, _)| c)
+    }
+}
+
+impl Default for Legend {
+    fn default() -> Self {
+        let mut legend = Legend::new();
+        legend.insert(' ', Glyph::Terrain(Terrain::Floor));
+        legend.insert('.', Glyph::Terrain(Terrain::Floor));
+        legend.insert('W', Glyph::Terrain(Terrain::Wall));
+        legend.insert('~', Glyph::Terrain(Terrain::Water));
+        legend.insert('"', Glyph::Terrain(Terrain::Grass));
+        legend.insert('+', Glyph::Terrain(Terrain::Door));
+        legend.insert(',', Glyph::Terrain(Terrain::Dirt));
+        legend.insert('<', Glyph::Stairs(Stairs::Up));
+        legend.insert('>', Glyph::Stairs(Stairs::Down));
+        legend.insert('@', Glyph::Spawn(Spawn::Player));
+        for c in 'a'..='z' {
+            legend.insert(c, Glyph::Spawn(Spawn::Hostile(c)));
+        }
+        legend
+    }
+}
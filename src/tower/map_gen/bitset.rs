@@ -0,0 +1,107 @@
+const BLOCK_BITS: usize = u64::BITS as usize;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bitset {
+    len: usize,
+    blocks: Vec<u64>,
+}
+
+impl Bitset {
+    pub fn new(len: usize) -> Self {
+        Bitset {
+            len,
+            blocks: vec![0; len.div_ceil(BLOCK_BITS)],
+        }
+    }
+
+    pub fn full(len: usize) -> Self {
+        let mut bitset = Bitset::new(len);
+        for block in bitset.blocks.iter_mut() {
+            *block = u64::MAX;
+        }
+        if !len.is_multiple_of(BLOCK_BITS) {
+            if let Some(last) = bitset.blocks.last_mut() {
+                *last = (1 << (len % BLOCK_BITS)) - 1;
+            }
+        }
+        bitset
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        index < self.len && self.blocks[index / BLOCK_BITS] & (1 << (index % BLOCK_BITS)) != 0
+    }
+
+    pub fn insert(&mut self, index: usize) {
+        if index < self.len {
+            self.blocks[index / BLOCK_BITS] |= 1 << (index % BLOCK_BITS);
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.len {
+            self.blocks[index / BLOCK_BITS] &= !(1 << (index % BLOCK_BITS));
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.blocks
+            .iter()
+            .map(|block| block.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn intersects(&self, other: &Bitset) -> bool {
+        self.blocks
+            .iter()
+            .zip(other.blocks.iter())
+            .any(|(a, b)| a & b != 0)
+    }
+
+    pub fn is_subset(&self, other: &Bitset) -> bool {
+        self.blocks
+            .iter()
+            .zip(other.blocks.iter())
+            .all(|(a, b)| a & !b == 0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.blocks.iter().enumerate().flat_map(|(i, &block)| {
+            let mut block = block;
+            std::iter::from_fn(move || {
+                if block == 0 {
+                    return None;
+                }
+                let bit = block.trailing_zeros() as usize;
+                block &= block - 1;
+                Some(i * BLOCK_BITS + bit)
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitset() {
+        let mut bitset = Bitset::full(70);
+        assert_eq!(bitset.count(), 70);
+        assert!(bitset.contains(69));
+        assert!(!bitset.contains(70));
+        bitset.remove(0);
+        bitset.remove(64);
+        assert_eq!(bitset.count(), 68);
+        assert_eq!(bitset.iter().take(2).collect::<Vec<usize>>(), vec![1, 2]);
+        let mut bitset = Bitset::new(100);
+        assert_eq!(bitset.count(), 0);
+        bitset.insert(3);
+        bitset.insert(99);
+        assert_eq!(bitset.iter().collect::<Vec<usize>>(), vec![3, 99]);
+        let mut other = Bitset::new(100);
+        other.insert(99);
+        assert!(bitset.intersects(&other));
+        assert!(other.is_subset(&bitset));
+        assert!(!bitset.is_subset(&other));
+    }
+}
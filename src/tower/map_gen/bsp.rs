@@ -0,0 +1,198 @@
+use rand::{rngs::StdRng, Rng};
+
+use crate::{
+    position::Position,
+    tower::{grid::Grid, tile::Tile},
+};
+
+use super::{is_interior, walls_to_grid, MapGenerator};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rect {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl Rect {
+    fn center(&self) -> Position {
+        Position::new(self.x + self.width / 2, self.y + self.height / 2)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BspGenerator {
+    pub min_leaf: usize,
+    pub min_room: usize,
+    pub max_depth: usize,
+}
+
+impl Default for BspGenerator {
+    fn default() -> Self {
+        BspGenerator {
+            min_leaf: 8,
+            min_room: 4,
+            max_depth: 6,
+        }
+    }
+}
+
+impl BspGenerator {
+    fn split(&self, area: Rect, depth: usize, rng: &mut StdRng) -> Option<(Rect, Rect)> {
+        if depth >= self.max_depth {
+            return None;
+        }
+        let min_leaf = self.min_leaf.max(3) as i32;
+        let horizontal = match (area.height >= min_leaf * 2, area.width >= min_leaf * 2) {
+            (false, false) => return None,
+            (true, false) => true,
+            (false, true) => false,
+            (true, true) if area.width == area.height => rng.gen(),
+            (true, true) => area.height > area.width,
+        };
+        match horizontal {
+            true => {
+                let cut = rng.gen_range(min_leaf..=area.height - min_leaf);
+                Some((
+                    Rect {
+                        height: cut,
+                        ..area
+                    },
+                    Rect {
+                        y: area.y + cut,
+                        height: area.height - cut,
+                        ..area
+                    },
+                ))
+            }
+            false => {
+                let cut = rng.gen_range(min_leaf..=area.width - min_leaf);
+                Some((
+                    Rect { width: cut, ..area },
+                    Rect {
+                        x: area.x + cut,
+                        width: area.width - cut,
+                        ..area
+                    },
+                ))
+            }
+        }
+    }
+
+    fn build(
+        &self,
+        area: Rect,
+        depth: usize,
+        rng: &mut StdRng,
+        walls: &mut Grid<bool>,
+    ) -> Position {
+        match self.split(area, depth, rng) {
+            Some((a, b)) => {
+                let from = self.build(a, depth + 1, rng, walls);
+                let to = self.build(b, depth + 1, rng, walls);
+                carve_corridor(walls, from, to, rng);
+                match rng.gen() {
+                    true => from,
+                    false => to,
+                }
+            }
+            None => {
+                let room = self.room(area, rng);
+                carve_rect(walls, room);
+                room.center()
+            }
+        }
+    }
+
+    fn room(&self, area: Rect, rng: &mut StdRng) -> Rect {
+        let max_width = (area.width - 2).max(1);
+        let max_height = (area.height - 2).max(1);
+        let width = rng.gen_range((self.min_room as i32).clamp(1, max_width)..=max_width);
+        let height = rng.gen_range((self.min_room as i32).clamp(1, max_height)..=max_height);
+        Rect {
+            x: area.x + 1 + rng.gen_range(0..=max_width - width),
+            y: area.y + 1 + rng.gen_range(0..=max_height - height),
+            width,
+            height,
+        }
+    }
+}
+
+fn carve(walls: &mut Grid<bool>, pos: Position) {
+    if !is_interior(walls, &pos) {
+        return;
+    }
+    if let Some(wall) = walls.get_mut(&pos) {
+        *wall = false;
+    }
+}
+
+fn carve_rect(walls: &mut Grid<bool>, rect: Rect) {
+    for y in rect.y..rect.y + rect.height {
+        for x in rect.x..rect.x + rect.width {
+            carve(walls, Position::new(x, y));
+        }
+    }
+}
+
+fn carve_corridor(walls: &mut Grid<bool>, from: Position, to: Position, rng: &mut StdRng) {
+    let corner = match rng.gen() {
+        true => Position::new(to.x, from.y),
+        false => Position::new(from.x, to.y),
+    };
+    for (a, b) in [(from, corner), (corner, to)] {
+        for y in a.y.min(b.y)..=a.y.max(b.y) {
+            for x in a.x.min(b.x)..=a.x.max(b.x) {
+                carve(walls, Position::new(x, y));
+            }
+        }
+    }
+}
+
+impl MapGenerator for BspGenerator {
+    fn generate(&self, width: usize, height: usize, rng: &mut StdRng) -> Option<Grid<Tile>> {
+        if width < 3 || height < 3 {
+            return None;
+        }
+        let mut walls: Grid<bool> = Grid::filled(width, height, true);
+        let area = Rect {
+            x: 0,
+            y: 0,
+            width: width as i32,
+            height: height as i32,
+        };
+        self.build(area, 0, rng, &mut walls);
+        Some(walls_to_grid(&walls))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::tower::map_gen::largest_region;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_bsp_generator() {
+        let generator = BspGenerator::default();
+        let grid = generator
+            .generate(40, 30, &mut StdRng::seed_from_u64(3))
+            .unwrap();
+        let walls = grid.map(|tile| !tile.walkable());
+        let floors = walls.iter().filter(|&&wall| !wall).count();
+        assert!(floors > 40 * 30 / 4);
+        assert_eq!(largest_region(&walls).len(), floors);
+        assert!(walls
+            .positions()
+            .all(|pos| is_interior(&walls, &pos) || *walls.get(&pos).unwrap()));
+
+        let again = generator
+            .generate(40, 30, &mut StdRng::seed_from_u64(3))
+            .unwrap();
+        assert!(again
+            .iter()
+            .map(|tile| !tile.walkable())
+            .eq(walls.iter().copied()));
+    }
+}
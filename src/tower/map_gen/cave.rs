@@ -0,0 +1,144 @@
+use rand::{rngs::StdRng, Rng};
+
+use crate::{
+    position::Position,
+    tower::{grid::Grid, tile::Tile},
+};
+
+use super::{is_interior, largest_region, walls_to_grid, MapGenerator};
+
+#[derive(Debug, Clone, Copy)]
+pub struct CaveGenerator {
+    pub fill: f64,
+    pub birth: usize,
+    pub survival: usize,
+    pub iterations: usize,
+}
+
+impl Default for CaveGenerator {
+    fn default() -> Self {
+        CaveGenerator {
+            fill: 0.45,
+            birth: 5,
+            survival: 4,
+            iterations: 4,
+        }
+    }
+}
+
+fn wall_neighbors(walls: &Grid<bool>, pos: &Position) -> usize {
+    let mut count = 0;
+    for y in -1..=1 {
+        for x in -1..=1 {
+            if x == 0 && y == 0 {
+                continue;
+            }
+            if *walls.get(&(*pos + Position::new(x, y))).unwrap_or(&true) {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+impl CaveGenerator {
+    fn step(&self, walls: &Grid<bool>) -> Grid<bool> {
+        let mut next = walls.clone();
+        for pos in walls.positions() {
+            if !is_interior(walls, &pos) {
+                continue;
+            }
+            let neighbors = wall_neighbors(walls, &pos);
+            *next.get_mut(&pos).unwrap() = match walls.get(&pos).unwrap() {
+                true => neighbors >= self.survival,
+                false => neighbors >= self.birth,
+            };
+        }
+        next
+    }
+}
+
+impl MapGenerator for CaveGenerator {
+    fn generate(&self, width: usize, height: usize, rng: &mut StdRng) -> Option<Grid<Tile>> {
+        let mut walls: Grid<bool> = Grid::filled(width, height, true);
+        for pos in walls.positions() {
+            if is_interior(&walls, &pos) {
+                *walls.get_mut(&pos).unwrap() = rng.gen_bool(self.fill.clamp(0., 1.));
+            }
+        }
+        for _ in 0..self.iterations {
+            walls = self.step(&walls);
+        }
+        let cave = largest_region(&walls);
+        if cave.is_empty() {
+            return None;
+        }
+        walls = Grid::filled(width, height, true);
+        for pos in cave {
+            *walls.get_mut(&pos).unwrap() = false;
+        }
+        Some(walls_to_grid(&walls))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_cave_generator() {
+        let generator = CaveGenerator::default();
+        let grid = generator
+            .generate(50, 40, &mut StdRng::seed_from_u64(9))
+            .unwrap();
+        let walls = grid.map(|tile| !tile.walkable());
+        let floors = walls.iter().filter(|&&wall| !wall).count();
+        assert!(floors > 50 * 40 / 5);
+        assert_eq!(largest_region(&walls).len(), floors);
+        assert!(walls
+            .positions()
+            .all(|pos| is_interior(&walls, &pos) || *walls.get(&pos).unwrap()));
+
+        let again = generator
+            .generate(50, 40, &mut StdRng::seed_from_u64(9))
+            .unwrap();
+        assert!(again
+            .iter()
+            .map(|tile| !tile.walkable())
+            .eq(walls.iter().copied()));
+    }
+
+    #[test]
+    fn test_cave_generator_rules() {
+        let generate = |generator: CaveGenerator| {
+            generator
+                .generate(50, 40, &mut StdRng::seed_from_u64(9))
+                .unwrap()
+                .map(|tile| !tile.walkable())
+        };
+        let default = CaveGenerator::default();
+        let walls = generate(default);
+        let floors = |walls: &Grid<bool>| walls.iter().filter(|&&wall| !wall).count();
+
+        let open = generate(CaveGenerator {
+            birth: 8,
+            survival: 8,
+            ..default
+        });
+        assert!(floors(&open) > floors(&walls));
+
+        let closed = generate(CaveGenerator {
+            birth: 4,
+            survival: 3,
+            ..default
+        });
+        assert!(floors(&closed) < floors(&walls));
+
+        let rough = generate(CaveGenerator {
+            iterations: 0,
+            ..default
+        });
+        assert!(!rough.iter().eq(walls.iter()));
+    }
+}
@@ -0,0 +1,104 @@
+use rand::{rngs::StdRng, seq::SliceRandom};
+
+use crate::{
+    position::{Position, DIRECTIONS},
+    tower::{grid::Grid, tile::Tile},
+};
+
+use super::{is_interior, walls_to_grid, MapGenerator};
+
+#[derive(Debug, Clone, Copy)]
+pub struct DrunkardGenerator {
+    pub floor_percent: f64,
+    pub lifetime: usize,
+    pub max_walkers: usize,
+}
+
+impl Default for DrunkardGenerator {
+    fn default() -> Self {
+        DrunkardGenerator {
+            floor_percent: 0.4,
+            lifetime: 200,
+            max_walkers: 500,
+        }
+    }
+}
+
+impl MapGenerator for DrunkardGenerator {
+    fn generate(&self, width: usize, height: usize, rng: &mut StdRng) -> Option<Grid<Tile>> {
+        if width < 3 || height < 3 {
+            return None;
+        }
+        let mut walls: Grid<bool> = Grid::filled(width, height, true);
+        let interior = (width - 2) * (height - 2);
+        let target = ((interior as f64 * self.floor_percent.clamp(0., 1.)) as usize).max(1);
+        let start = Position::new(width as i32 / 2, height as i32 / 2);
+        *walls.get_mut(&start)? = false;
+        let mut floors = vec![start];
+        for _ in 0..self.max_walkers {
+            if floors.len() >= target {
+                break;
+            }
+            let mut pos = *floors.choose(rng)?;
+            for _ in 0..self.lifetime {
+                let next = pos + *DIRECTIONS.choose(rng)?;
+                if !is_interior(&walls, &next) {
+                    continue;
+                }
+                pos = next;
+                let wall = walls.get_mut(&pos)?;
+                if *wall {
+                    *wall = false;
+                    floors.push(pos);
+                    if floors.len() >= target {
+                        break;
+                    }
+                }
+            }
+        }
+        Some(walls_to_grid(&walls))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::tower::map_gen::largest_region;
+    use rand::SeedableRng;
+
+    fn floors(generator: DrunkardGenerator) -> Grid<bool> {
+        generator
+            .generate(60, 40, &mut StdRng::seed_from_u64(4))
+            .unwrap()
+            .map(|tile| tile.walkable())
+    }
+
+    #[test]
+    fn test_drunkard_floor_percent() {
+        for floor_percent in [0.1, 0.25, 0.4] {
+            let floors = floors(DrunkardGenerator {
+                floor_percent,
+                ..DrunkardGenerator::default()
+            });
+            let count = floors.iter().filter(|&&floor| floor).count();
+            assert_eq!(count, (58. * 38. * floor_percent) as usize);
+            let walls = floors.map(|floor| !floor);
+            assert_eq!(largest_region(&walls).len(), count);
+            assert!(walls
+                .positions()
+                .all(|pos| is_interior(&walls, &pos) || *walls.get(&pos).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_drunkard_walkers() {
+        let floors = floors(DrunkardGenerator {
+            floor_percent: 1.,
+            lifetime: 3,
+            max_walkers: 5,
+        });
+        let count = floors.iter().filter(|&&floor| floor).count();
+        assert!(count > 1);
+        assert!(count <= 1 + 3 * 5);
+    }
+}
@@ -1 +1,60 @@
+use rand::rngs::StdRng;
+
+use crate::position::{Position, DIRECTIONS};
+
+use super::{
+    grid::Grid,
+    tile::{Terrain, Tile},
+};
+
+pub mod bitset;
+pub mod bsp;
+pub mod cave;
+pub mod drunkard;
+pub mod overlapping;
+pub mod tileset;
+pub mod tunneler;
 pub mod wfc;
+
+pub trait MapGenerator {
+    fn generate(&self, width: usize, height: usize, rng: &mut StdRng) -> Option<Grid<Tile>>;
+}
+
+fn is_interior(grid: &Grid<bool>, pos: &Position) -> bool {
+    pos.x > 0 && pos.y > 0 && pos.x < grid.width as i32 - 1 && pos.y < grid.height as i32 - 1
+}
+
+fn largest_region(walls: &Grid<bool>) -> Vec<Position> {
+    let mut seen: Grid<bool> = Grid::new(walls.width, walls.height);
+    let mut largest: Vec<Position> = Vec::new();
+    for start in walls.positions() {
+        if *walls.get(&start).unwrap() || *seen.get(&start).unwrap() {
+            continue;
+        }
+        *seen.get_mut(&start).unwrap() = true;
+        let mut region = vec![start];
+        let mut open = vec![start];
+        while let Some(pos) = open.pop() {
+            for dir in DIRECTIONS {
+                let next = pos + *dir;
+                if walls.get(&next) != Some(&false) || *seen.get(&next).unwrap() {
+                    continue;
+                }
+                *seen.get_mut(&next).unwrap() = true;
+                region.push(next);
+                open.push(next);
+            }
+        }
+        if region.len() > largest.len() {
+            largest = region;
+        }
+    }
+    largest
+}
+
+fn walls_to_grid(walls: &Grid<bool>) -> Grid<Tile> {
+    walls.map(|&wall| match wall {
+        true => Tile::new(Terrain::Wall),
+        false => Tile::new(Terrain::Floor),
+    })
+}
@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+
+use rand::{rngs::StdRng, Rng};
+
+use crate::{
+    constants::WFC_MAX_ATTEMPTS,
+    position::{Position, DIRECTIONS},
+    tower::{
+        grid::Grid,
+        tile::{Terrain, Tile},
+        tower::terrain_from_str,
+    },
+};
+
+use super::{wfc::WFC, MapGenerator};
+
+#[derive(Debug, Clone, Copy)]
+pub struct OverlappingOptions {
+    pub n: usize,
+    pub periodic: bool,
+    pub rotations: bool,
+    pub reflections: bool,
+}
+
+impl Default for OverlappingOptions {
+    fn default() -> Self {
+        OverlappingOptions {
+            n: 3,
+            periodic: false,
+            rotations: false,
+            reflections: false,
+        }
+    }
+}
+
+pub struct OverlappingModel<T> {
+    n: usize,
+    tiles: Vec<T>,
+    patterns: Vec<Vec<usize>>,
+    weights: Vec<usize>,
+}
+
+impl<T: Copy + PartialEq> OverlappingModel<T> {
+    pub fn new(sample: &Grid<T>, options: OverlappingOptions) -> Self {
+        let n = options.n.max(1);
+        let mut tiles: Vec<T> = Vec::new();
+        let indexes = sample.map(|&tile| match tiles.iter().position(|&t| t == tile) {
+            Some(index) => index,
+            None => {
+                tiles.push(tile);
+                tiles.len() - 1
+            }
+        });
+        let (max_x, max_y) = match options.periodic {
+            true => (sample.width, sample.height),
+            false => (
+                (sample.width + 1).saturating_sub(n),
+                (sample.height + 1).saturating_sub(n),
+            ),
+        };
+        let mut lookup: HashMap<Vec<usize>, usize> = HashMap::new();
+        let mut patterns: Vec<Vec<usize>> = Vec::new();
+        let mut weights: Vec<usize> = Vec::new();
+        for y in 0..max_y {
+            for x in 0..max_x {
+                let pattern: Vec<usize> = (0..n * n)
+                    .map(|i| {
+                        let pos = Position::new(
+                            ((x + i % n) % sample.width) as i32,
+                            ((y + i / n) % sample.height) as i32,
+                        );
+                        *indexes.get(&pos).unwrap()
+                    })
+                    .collect();
+                for variant in variants(pattern, n, options) {
+                    match lookup.get(&variant) {
+                        Some(&index) => weights[index] += 1,
+                        None => {
+                            lookup.insert(variant.clone(), patterns.len());
+                            patterns.push(variant);
+                            weights.push(1);
+                        }
+                    }
+                }
+            }
+        }
+        OverlappingModel {
+            n,
+            tiles,
+            patterns,
+            weights,
+        }
+    }
+
+    pub fn pattern_count(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn tile(&self, pattern: usize) -> T {
+        self.tiles[self.patterns[pattern][0]]
+    }
+
+    pub fn constraints(&self) -> Vec<Vec<(Position, Vec<usize>)>> {
+        self.patterns
+            .iter()
+            .map(|p| {
+                DIRECTIONS
+                    .iter()
+                    .map(|dir| {
+                        let allowed: Vec<usize> = self
+                            .patterns
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, q)| self.agrees(p, q, dir))
+                            .map(|(i, _)| i)
+                            .collect();
+                        (*dir, allowed)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn wfc(&self, width: usize, height: usize, seed: u64) -> WFC {
+        WFC::new(
+            width,
+            height,
+            self.constraints(),
+            self.weights.clone(),
+            seed,
+        )
+        .with_max_attempts(WFC_MAX_ATTEMPTS)
+    }
+
+    pub fn output(&self, wfc: &WFC) -> Option<Grid<T>> {
+        let mut output = Grid::filled(wfc.cells.width, wfc.cells.height, self.tiles[0]);
+        for pos in wfc.cells.positions() {
+            let pattern = wfc.cells.get(&pos)?.chosen_index?;
+            *output.get_mut(&pos)? = self.tile(pattern);
+        }
+        Some(output)
+    }
+
+    fn agrees(&self, p: &[usize], q: &[usize], dir: &Position) -> bool {
+        let n = self.n as i32;
+        for y in 0..n {
+            for x in 0..n {
+                let (qx, qy) = (x - dir.x, y - dir.y);
+                if qx < 0 || qx >= n || qy < 0 || qy >= n {
+                    continue;
+                }
+                if p[(y * n + x) as usize] != q[(qy * n + qx) as usize] {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+impl MapGenerator for OverlappingModel<Terrain> {
+    fn generate(&self, width: usize, height: usize, rng: &mut StdRng) -> Option<Grid<Tile>> {
+        let walkable: Vec<usize> = (0..self.pattern_count())
+            .filter(|&pattern| self.tile(pattern).walkable())
+            .collect();
+        let mut wfc = self.wfc(width, height, rng.gen());
+        wfc.require_connected(&walkable);
+        wfc.run().ok()?;
+        let output = self.output(&wfc)?;
+        let mut grid: Grid<Tile> = Grid::new(width, height);
+        for pos in output.positions() {
+            grid.get_mut(&pos)?.set_terrain(*output.get(&pos)?);
+        }
+        Some(grid)
+    }
+}
+
+pub fn sample_from_str(s: &str) -> Grid<Terrain> {
+    let rows: Vec<&str> = s
+        .lines()
+        .map(str::trim)
+        .filter(|row| row.starts_with('|'))
+        .collect();
+    let width = rows
+        .iter()
+        .map(|row| row.trim_start_matches('|').split_terminator('|').count())
+        .max()
+        .unwrap_or(0);
+    terrain_from_str(width, rows.len(), s)
+}
+
+fn variants(pattern: Vec<usize>, n: usize, options: OverlappingOptions) -> Vec<Vec<usize>> {
+    let transform = |p: &[usize], f: &dyn Fn(usize, usize) -> (usize, usize)| -> Vec<usize> {
+        (0..n * n)
+            .map(|i| {
+                let (x, y) = f(i % n, i / n);
+                p[y * n + x]
+            })
+            .collect()
+    };
+    let mut variants = vec![pattern];
+    if options.reflections {
+        let reflected = transform(&variants[0], &|x, y| (n - 1 - x, y));
+        variants.push(reflected);
+    }
+    if options.rotations {
+        let mut rotations: Vec<Vec<usize>> = Vec::new();
+        for variant in variants.iter() {
+            let mut rotated = variant.clone();
+            for _ in 0..3 {
+                rotated = transform(&rotated, &|x, y| (n - 1 - y, x));
+                rotations.push(rotated.clone());
+            }
+        }
+        variants.extend(rotations);
+    }
+    variants
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::constants::RUINS_SAMPLE;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_overlapping_checkerboard() {
+        let sample = terrain_from_str(
+            4,
+            4,
+            "
+        |W| |W| |
+        | |W| |W|
+        |W| |W| |
+        | |W| |W|
+            ",
+        );
+        let options = OverlappingOptions {
+            n: 2,
+            periodic: true,
+            ..OverlappingOptions::default()
+        };
+        let model = OverlappingModel::new(&sample, options);
+        assert_eq!(model.pattern_count(), 2);
+        let mut wfc = model.wfc(8, 8, 0);
+        wfc.run().unwrap();
+        let output = model.output(&wfc).unwrap();
+        for pos in output.positions() {
+            let terrain = *output.get(&pos).unwrap();
+            assert!(terrain == Terrain::Wall || terrain == Terrain::Floor);
+            for dir in DIRECTIONS {
+                if let Some(&neighbor) = output.get(&(pos + *dir)) {
+                    assert_ne!(terrain, neighbor);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_overlapping_symmetry() {
+        let sample = terrain_from_str(
+            2,
+            2,
+            "
+        |W| |
+        |W| |
+            ",
+        );
+        let options = OverlappingOptions {
+            n: 2,
+            periodic: true,
+            ..OverlappingOptions::default()
+        };
+        assert_eq!(OverlappingModel::new(&sample, options).pattern_count(), 2);
+        let options = OverlappingOptions {
+            rotations: true,
+            ..options
+        };
+        assert_eq!(OverlappingModel::new(&sample, options).pattern_count(), 4);
+    }
+
+    #[test]
+    fn test_ruins_sample() {
+        let sample = sample_from_str(RUINS_SAMPLE);
+        assert_eq!((sample.width, sample.height), (14, 11));
+        assert_eq!(sample.get(&Position::new(1, 1)), Some(&Terrain::Floor));
+        let model = OverlappingModel::new(&sample, OverlappingOptions::default());
+        let grid = model
+            .generate(30, 20, &mut StdRng::seed_from_u64(3))
+            .unwrap();
+        assert!(grid.iter().any(|tile| tile.walkable()));
+        assert!(grid.iter().any(|tile| !tile.walkable()));
+    }
+}
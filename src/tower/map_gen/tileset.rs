@@ -0,0 +1,339 @@
+use std::fmt;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use rand::{rngs::StdRng, Rng};
+use serde::Deserialize;
+
+use crate::{
+    constants::WFC_MAX_ATTEMPTS,
+    position::Position,
+    tower::{
+        grid::Grid,
+        tile::{Terrain, Tile},
+    },
+};
+
+use super::{
+    wfc::{infer_constraints, Constraints, WFC},
+    MapGenerator,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Neighbors {
+    pub up: Vec<String>,
+    pub down: Vec<String>,
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+}
+
+impl Neighbors {
+    fn directions(&self) -> [(Position, &Vec<String>); 4] {
+        [
+            (Position::UP, &self.up),
+            (Position::DOWN, &self.down),
+            (Position::LEFT, &self.left),
+            (Position::RIGHT, &self.right),
+        ]
+    }
+    fn get(&self, dir: Position) -> &Vec<String> {
+        match dir {
+            Position::UP => &self.up,
+            Position::DOWN => &self.down,
+            Position::LEFT => &self.left,
+            _ => &self.right,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TilesetTile {
+    pub name: String,
+    pub terrain: Terrain,
+    pub weight: usize,
+    pub neighbors: Neighbors,
+}
+
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "2fb2b0c1-6106-4c52-9ac9-f9ea68261c67"]
+pub struct Tileset {
+    pub tiles: Vec<TilesetTile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TilesetIssue {
+    Empty,
+    DuplicateTile(String),
+    UnknownNeighbor {
+        tile: String,
+        neighbor: String,
+    },
+    Asymmetric {
+        tile: String,
+        dir: Position,
+        neighbor: String,
+    },
+}
+
+impl TilesetIssue {
+    pub fn is_error(&self) -> bool {
+        !matches!(self, TilesetIssue::Asymmetric { .. })
+    }
+}
+
+impl fmt::Display for TilesetIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TilesetIssue::Empty => write!(f, "tileset has no tiles"),
+            TilesetIssue::DuplicateTile(name) => write!(f, "tile {} is defined twice", name),
+            TilesetIssue::UnknownNeighbor { tile, neighbor } => {
+                write!(f, "tile {} allows unknown neighbor {}", tile, neighbor)
+            }
+            TilesetIssue::Asymmetric {
+                tile,
+                dir,
+                neighbor,
+            } => write!(
+                f,
+                "tile {} allows {} at ({}, {}) but {} does not allow {} back",
+                tile, neighbor, dir.x, dir.y, neighbor, tile
+            ),
+        }
+    }
+}
+
+impl Tileset {
+    pub fn from_example(example: &Grid<Terrain>) -> Tileset {
+        let mut terrains: Vec<Terrain> = Vec::new();
+        for terrain in example.iter() {
+            if !terrains.contains(terrain) {
+                terrains.push(*terrain);
+            }
+        }
+        let indices = example.map(|terrain| terrains.iter().position(|t| t == terrain).unwrap());
+        let (constraints, distributions) = infer_constraints(&indices);
+        let tiles = terrains
+            .iter()
+            .zip(constraints)
+            .zip(distributions)
+            .map(|((terrain, rules), weight)| {
+                let allowed = |dir: Position| -> Vec<String> {
+                    rules
+                        .iter()
+                        .filter(|(d, _)| *d == dir)
+                        .flat_map(|(_, allowed)| allowed)
+                        .map(|&index| terrains[index].name().to_string())
+                        .collect()
+                };
+                TilesetTile {
+                    name: terrain.name().to_string(),
+                    terrain: *terrain,
+                    weight,
+                    neighbors: Neighbors {
+                        up: allowed(Position::UP),
+                        down: allowed(Position::DOWN),
+                        left: allowed(Position::LEFT),
+                        right: allowed(Position::RIGHT),
+                    },
+                }
+            })
+            .collect();
+        Tileset { tiles }
+    }
+
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.tiles.iter().position(|tile| tile.name == name)
+    }
+
+    pub fn terrains(&self) -> Vec<Terrain> {
+        self.tiles.iter().map(|tile| tile.terrain).collect()
+    }
+
+    pub fn validate(&self) -> Vec<TilesetIssue> {
+        let mut issues: Vec<TilesetIssue> = Vec::new();
+        if self.tiles.is_empty() {
+            issues.push(TilesetIssue::Empty);
+        }
+        for (i, tile) in self.tiles.iter().enumerate() {
+            if self.index(&tile.name) != Some(i) {
+                issues.push(TilesetIssue::DuplicateTile(tile.name.clone()));
+            }
+            for (dir, names) in tile.neighbors.directions() {
+                for name in names {
+                    let neighbor = match self.index(name) {
+                        Some(index) => &self.tiles[index],
+                        None => {
+                            issues.push(TilesetIssue::UnknownNeighbor {
+                                tile: tile.name.clone(),
+                                neighbor: name.clone(),
+                            });
+                            continue;
+                        }
+                    };
+                    let back = Position::ZERO - dir;
+                    if !neighbor.neighbors.get(back).contains(&tile.name) {
+                        issues.push(TilesetIssue::Asymmetric {
+                            tile: tile.name.clone(),
+                            dir,
+                            neighbor: name.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        issues
+    }
+
+    pub fn constraints(&self) -> Constraints {
+        self.tiles
+            .iter()
+            .map(|tile| {
+                tile.neighbors
+                    .directions()
+                    .iter()
+                    .map(|(dir, names)| {
+                        let allowed = names.iter().filter_map(|name| self.index(name)).collect();
+                        (*dir, allowed)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn distributions(&self) -> Vec<usize> {
+        self.tiles.iter().map(|tile| tile.weight).collect()
+    }
+}
+
+impl MapGenerator for Tileset {
+    fn generate(&self, width: usize, height: usize, rng: &mut StdRng) -> Option<Grid<Tile>> {
+        let mut wfc = WFC::new(
+            width,
+            height,
+            self.constraints(),
+            self.distributions(),
+            rng.gen(),
+        )
+        .with_max_attempts(WFC_MAX_ATTEMPTS);
+        let terrains = self.terrains();
+        let walkable: Vec<usize> = (0..terrains.len())
+            .filter(|&index| terrains[index].walkable())
+            .collect();
+        wfc.require_connected(&walkable);
+        wfc.run().ok()?;
+        let mut grid: Grid<Tile> = Grid::new(width, height);
+        for pos in wfc.cells.positions() {
+            let index = wfc.cells.get(&pos)?.chosen_index?;
+            grid.get_mut(&pos)?.set_terrain(terrains[index]);
+        }
+        Some(grid)
+    }
+}
+
+#[derive(Default)]
+pub struct TilesetLoader;
+
+impl AssetLoader for TilesetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let tileset: Tileset = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(tileset));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tileset.ron"]
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        constants::MARSH_SAMPLE,
+        position::DIRECTIONS,
+        tower::map_gen::{largest_region, overlapping::sample_from_str},
+    };
+
+    #[test]
+    fn test_tileset_validation() {
+        let tileset: Tileset = ron::from_str(
+            r#"(
+                tiles: [
+                    (
+                        name: "dirt",
+                        terrain: Dirt,
+                        weight: 2,
+                        neighbors: (up: ["dirt"], down: ["dirt"], left: ["dirt", "grass"], right: ["dirt"]),
+                    ),
+                    (
+                        name: "grass",
+                        terrain: Grass,
+                        weight: 1,
+                        neighbors: (up: ["grass"], down: ["grass"], left: ["grass"], right: ["grass", "lava"]),
+                    ),
+                ],
+            )"#,
+        )
+        .unwrap();
+        let issues = tileset.validate();
+        assert_eq!(issues.len(), 2);
+        assert!(issues.contains(&TilesetIssue::UnknownNeighbor {
+            tile: "grass".to_string(),
+            neighbor: "lava".to_string(),
+        }));
+        assert!(issues.contains(&TilesetIssue::Asymmetric {
+            tile: "dirt".to_string(),
+            dir: Position::LEFT,
+            neighbor: "grass".to_string(),
+        }));
+        assert_eq!(issues.iter().filter(|issue| issue.is_error()).count(), 1);
+        assert_eq!(tileset.distributions(), vec![2, 1]);
+        assert_eq!(tileset.constraints()[0][2], (Position::LEFT, vec![0, 1]));
+        assert_eq!(tileset.constraints()[1][3], (Position::RIGHT, vec![1]));
+    }
+
+    #[test]
+    fn test_overworld_tileset() {
+        let bytes = include_bytes!("../../../assets/tilesets/overworld.tileset.ron");
+        let tileset: Tileset = ron::de::from_bytes(bytes).unwrap();
+        assert!(tileset.validate().is_empty());
+        let grid = tileset
+            .generate(8, 8, &mut rand::SeedableRng::seed_from_u64(0))
+            .unwrap();
+        assert!(grid.iter().all(|tile| tile.terrain != Terrain::Wall));
+    }
+
+    #[test]
+    fn test_tileset_from_example() {
+        let tileset = Tileset::from_example(&sample_from_str(MARSH_SAMPLE));
+        assert!(tileset.validate().is_empty());
+        assert_eq!(
+            tileset.terrains(),
+            vec![Terrain::Grass, Terrain::Dirt, Terrain::Water]
+        );
+        let water = &tileset.tiles[tileset.index("water").unwrap()];
+        assert!(!water.neighbors.up.contains(&"dirt".to_string()));
+
+        let grid = tileset
+            .generate(40, 30, &mut rand::SeedableRng::seed_from_u64(0))
+            .unwrap();
+        let walls = grid.map(|tile| !tile.walkable());
+        let floors = walls.iter().filter(|&&wall| !wall).count();
+        assert_eq!(largest_region(&walls).len(), floors);
+        assert!(grid
+            .positions()
+            .filter(|pos| grid.get(pos).unwrap().terrain == Terrain::Water)
+            .all(|pos| DIRECTIONS.iter().all(|dir| grid
+                .get(&(pos + *dir))
+                .is_none_or(|tile| tile.terrain != Terrain::Dirt))));
+    }
+}
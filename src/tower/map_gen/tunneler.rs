@@ -0,0 +1,156 @@
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
+
+use crate::{
+    position::{Position, DIRECTIONS},
+    tower::{grid::Grid, tile::Tile},
+};
+
+use super::{is_interior, walls_to_grid, MapGenerator};
+
+#[derive(Debug, Clone, Copy)]
+pub struct TunnelerGenerator {
+    pub tunnelers: usize,
+    pub lifetime: usize,
+    pub turn_chance: f64,
+    pub room_chance: f64,
+    pub min_room: usize,
+    pub max_room: usize,
+}
+
+impl Default for TunnelerGenerator {
+    fn default() -> Self {
+        TunnelerGenerator {
+            tunnelers: 8,
+            lifetime: 60,
+            turn_chance: 0.15,
+            room_chance: 0.5,
+            min_room: 3,
+            max_room: 7,
+        }
+    }
+}
+
+fn carve(walls: &mut Grid<bool>, pos: Position, floors: &mut Vec<Position>) {
+    if !is_interior(walls, &pos) {
+        return;
+    }
+    if let Some(wall) = walls.get_mut(&pos) {
+        if *wall {
+            *wall = false;
+            floors.push(pos);
+        }
+    }
+}
+
+fn turn(dir: Position, rng: &mut StdRng) -> Position {
+    match rng.gen_bool(0.5) {
+        true => Position::new(dir.y, dir.x),
+        false => Position::new(-dir.y, -dir.x),
+    }
+}
+
+impl TunnelerGenerator {
+    fn room(
+        &self,
+        walls: &mut Grid<bool>,
+        center: Position,
+        rng: &mut StdRng,
+        floors: &mut Vec<Position>,
+    ) {
+        let min = self.min_room.max(1);
+        let max = self.max_room.max(min);
+        let width = rng.gen_range(min..=max) as i32;
+        let height = rng.gen_range(min..=max) as i32;
+        let corner = center - Position::new(width / 2, height / 2);
+        for y in corner.y..corner.y + height {
+            for x in corner.x..corner.x + width {
+                carve(walls, Position::new(x, y), floors);
+            }
+        }
+    }
+}
+
+impl MapGenerator for TunnelerGenerator {
+    fn generate(&self, width: usize, height: usize, rng: &mut StdRng) -> Option<Grid<Tile>> {
+        if width < 3 || height < 3 {
+            return None;
+        }
+        let mut walls: Grid<bool> = Grid::filled(width, height, true);
+        let start = Position::new(width as i32 / 2, height as i32 / 2);
+        let mut floors: Vec<Position> = Vec::new();
+        carve(&mut walls, start, &mut floors);
+        for _ in 0..self.tunnelers {
+            let mut pos = *floors.choose(rng)?;
+            let mut dir = *DIRECTIONS.choose(rng)?;
+            for _ in 0..self.lifetime {
+                if rng.gen_bool(self.turn_chance.clamp(0., 1.)) {
+                    if rng.gen_bool(self.room_chance.clamp(0., 1.)) {
+                        self.room(&mut walls, pos, rng, &mut floors);
+                    }
+                    dir = turn(dir, rng);
+                }
+                let next = pos + dir;
+                if !is_interior(&walls, &next) {
+                    dir = turn(dir, rng);
+                    continue;
+                }
+                pos = next;
+                carve(&mut walls, pos, &mut floors);
+            }
+        }
+        Some(walls_to_grid(&walls))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::tower::map_gen::largest_region;
+    use rand::SeedableRng;
+
+    fn walls(generator: TunnelerGenerator) -> Grid<bool> {
+        generator
+            .generate(60, 40, &mut StdRng::seed_from_u64(6))
+            .unwrap()
+            .map(|tile| !tile.walkable())
+    }
+
+    fn floors(walls: &Grid<bool>) -> usize {
+        walls.iter().filter(|&&wall| !wall).count()
+    }
+
+    #[test]
+    fn test_tunneler_corridors() {
+        let generator = TunnelerGenerator {
+            room_chance: 0.,
+            ..TunnelerGenerator::default()
+        };
+        let walls = walls(generator);
+        assert!(floors(&walls) > generator.lifetime);
+        assert!(floors(&walls) <= 1 + generator.tunnelers * generator.lifetime);
+        assert_eq!(largest_region(&walls).len(), floors(&walls));
+    }
+
+    #[test]
+    fn test_tunneler_rooms() {
+        let generator = TunnelerGenerator {
+            tunnelers: 1,
+            lifetime: 10,
+            turn_chance: 1.,
+            room_chance: 1.,
+            min_room: 3,
+            max_room: 3,
+        };
+        let walls = walls(generator);
+        assert!(floors(&walls) > 1 + generator.tunnelers * generator.lifetime);
+        assert_eq!(largest_region(&walls).len(), floors(&walls));
+        assert!(walls
+            .positions()
+            .all(|pos| is_interior(&walls, &pos) || *walls.get(&pos).unwrap()));
+        let room = walls.positions().any(|corner| {
+            (0..3)
+                .all(|y| (0..3).all(|x| walls.get(&(corner + Position::new(x, y))) == Some(&false)))
+        });
+        assert!(room);
+    }
+}
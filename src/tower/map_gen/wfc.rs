@@ -1,160 +1,496 @@
 use std::{
     cmp::Ordering,
-    collections::{BinaryHeap, HashMap},
+    collections::{BTreeSet, BinaryHeap},
+    fmt,
 };
 
-use rand::{seq::SliceRandom, Rng};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::{
     position::{Position, DIRECTIONS},
     tower::grid::Grid,
 };
 
-#[derive(PartialEq)]
+use super::bitset::Bitset;
+
 struct EntropyPosition {
     entropy: f32,
     pos: Position,
 }
 
+impl PartialEq for EntropyPosition {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
 impl Eq for EntropyPosition {}
 
 impl Ord for EntropyPosition {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.entropy.total_cmp(&other.entropy)
+        other.entropy.total_cmp(&self.entropy)
     }
 }
 
 impl PartialOrd for EntropyPosition {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        other.entropy.partial_cmp(&self.entropy)
+        Some(self.cmp(other))
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Cell {
-    possible: Vec<bool>,
+    possible: Bitset,
+    remaining: usize,
     total_weight: f32,
     total_weight_log_weight: f32,
     entropy_noise: f32,
     pub chosen_index: Option<usize>,
 }
 
-impl Default for Cell {
-    fn default() -> Self {
+impl Cell {
+    fn new(possible: Bitset, total_weight: f32, total_weight_log_weight: f32, noise: f32) -> Self {
         Cell {
-            possible: Vec::new(),
-            total_weight: 0.,
-            total_weight_log_weight: 0.,
-            entropy_noise: 0.,
+            remaining: possible.count(),
+            possible,
+            total_weight,
+            total_weight_log_weight,
+            entropy_noise: noise,
             chosen_index: None,
         }
     }
-}
-
-impl Cell {
-    fn total_weight(&self, d: &Vec<usize>) -> f32 {
-        let mut total = 0.;
-        for (index, &possible) in self.possible.iter().enumerate() {
-            if possible {
-                total += *d.get(index).unwrap() as f32;
-            }
-        }
-        return total;
-    }
-    fn total_weight_log_weight(&self, d: &Vec<usize>) -> f32 {
-        self.possible
-            .iter()
-            .enumerate()
-            .map(|(index, &possible)| {
-                if possible {
-                    let rf = *d.get(index).unwrap() as f32;
-                    return rf * rf.log2();
-                } else {
-                    return 0.;
-                }
-            })
-            .sum()
-    }
-    fn set_entropy(&mut self, d: &Vec<usize>, noise: f32) {
-        self.total_weight = self.total_weight(d);
-        self.total_weight_log_weight = self.total_weight_log_weight(d);
-        self.entropy_noise = noise;
-    }
     fn entropy(&self) -> f32 {
         self.total_weight.log2() - self.total_weight_log_weight / self.total_weight
             + self.entropy_noise
     }
-    fn remove_index(&mut self, index: usize, d: &Vec<usize>) {
-        self.possible[index] = false;
-        let rf = *d.get(index).unwrap() as f32;
-        self.total_weight -= rf;
-        self.total_weight_log_weight -= rf * rf.log2();
+    fn remove_index(&mut self, index: usize, d: &[usize], weight_log_weights: &[f32]) {
+        if !self.possible.contains(index) {
+            return;
+        }
+        self.possible.remove(index);
+        self.remaining -= 1;
+        self.total_weight -= d[index] as f32;
+        self.total_weight_log_weight -= weight_log_weights[index];
     }
-    fn collapse(&mut self, chosen_index: usize, d: &Vec<usize>) {
-        self.chosen_index = Some(chosen_index);
-        let index_removals: Vec<usize> = self
-            .possible
-            .iter()
-            .enumerate()
-            .filter(|(i, &s)| *i != chosen_index && s)
-            .map(|(i, _)| i)
-            .collect();
-        for index in index_removals {
-            self.remove_index(index, d);
+    fn is_contradiction(&self) -> bool {
+        self.remaining == 0
+    }
+    fn choose_possible(&self, d: &[usize], rng: &mut impl Rng) -> Option<usize> {
+        let total: usize = self.possible.iter().map(|index| d[index]).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = rng.gen_range(0..total);
+        for index in self.possible.iter() {
+            if roll < d[index] {
+                return Some(index);
+            }
+            roll -= d[index];
         }
+        None
     }
-    fn choose_possible(&self, d: &Vec<usize>) -> Option<usize> {
-        let mut choices: Vec<usize> = Vec::new();
-        for (index, &status) in self.possible.iter().enumerate() {
-            if status {
-                let dist = *d.get(index).unwrap();
-                for _ in 0..dist {
-                    choices.push(index);
-                }
+}
+
+pub type Constraints = Vec<Vec<(Position, Vec<usize>)>>;
+
+pub fn infer_constraints(example: &Grid<usize>) -> (Constraints, Vec<usize>) {
+    let tile_count = example.iter().max().map_or(0, |&max| max + 1);
+    let mut distributions: Vec<usize> = vec![0; tile_count];
+    let mut neighbors: Vec<Vec<BTreeSet<usize>>> =
+        vec![vec![BTreeSet::new(); DIRECTIONS.len()]; tile_count];
+    for pos in example.positions() {
+        let tile = *example.get(&pos).unwrap();
+        distributions[tile] += 1;
+        for (i, dir) in DIRECTIONS.iter().enumerate() {
+            if let Some(&neighbor) = example.get(&(pos + *dir)) {
+                neighbors[tile][i].insert(neighbor);
+            }
+        }
+    }
+    let constraints = neighbors
+        .into_iter()
+        .map(|sets| {
+            DIRECTIONS
+                .iter()
+                .zip(sets)
+                .map(|(dir, set)| (*dir, set.into_iter().collect()))
+                .collect()
+        })
+        .collect();
+    (constraints, distributions)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WFCError {
+    Contradiction { pos: Position, attempts: usize },
+    PinContradiction { pos: Position },
+}
+
+impl fmt::Display for WFCError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WFCError::Contradiction { pos, attempts } => write!(
+                f,
+                "no tile fits at ({}, {}) after {} attempts, the rules may be unsatisfiable",
+                pos.x, pos.y, attempts
+            ),
+            WFCError::PinContradiction { pos } => {
+                write!(f, "pinned cells leave no tile for ({}, {})", pos.x, pos.y)
             }
         }
-        choices.choose(&mut rand::thread_rng()).copied()
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WFCEvent {
+    Collapsed {
+        pos: Position,
+        tile: usize,
+    },
+    Banned {
+        pos: Position,
+        tile: usize,
+        entropy: f32,
+    },
+    Restarted {
+        pos: Position,
+        attempts: usize,
+    },
+}
+
+struct Connectivity {
+    walkable: Bitset,
+    required: Vec<Position>,
+    all: bool,
+    dirty: bool,
+}
+
+#[derive(Clone, Copy)]
+struct TileCount {
+    tile: usize,
+    min: usize,
+    max: usize,
+}
+
 pub struct WFC {
     pub cells: Grid<Cell>,
     pub uncollapsed_cells: usize,
-    constraints: Vec<Vec<(Position, Vec<usize>)>>,
+    propagator: Vec<Vec<Vec<usize>>>,
+    supports: Vec<[u32; 4]>,
+    compatible: Vec<[u32; 4]>,
     distributions: Vec<usize>,
+    weight_log_weights: Vec<f32>,
     entropy_heap: BinaryHeap<EntropyPosition>,
+    stack: Vec<(Position, usize)>,
+    rng: StdRng,
+    pins: Vec<(Position, Vec<usize>)>,
+    connectivity: Option<Connectivity>,
+    counts: Vec<TileCount>,
+    possible_counts: Vec<usize>,
+    decided_counts: Vec<usize>,
+    contradiction: Option<Position>,
+    events: Option<Vec<WFCEvent>>,
+    attempts: usize,
+    max_attempts: usize,
+    failed: bool,
 }
 
 impl WFC {
     pub fn new(
         width: usize,
         height: usize,
-        constraints: Vec<Vec<(Position, Vec<usize>)>>,
+        constraints: Constraints,
         distributions: Vec<usize>,
+        seed: u64,
     ) -> Self {
-        let mut entropy_heap: BinaryHeap<EntropyPosition> = BinaryHeap::new();
-        let mut cells: Grid<Cell> = Grid::new(width, height);
-        let mut rng = rand::thread_rng();
-        for y in 0..height {
-            for x in 0..width {
-                let pos = Position::new(x as i32, y as i32);
-                let cell = cells.get_mut(&pos).unwrap();
-                cell.possible.resize(distributions.len(), true);
-                cell.set_entropy(&distributions, rng.gen_range(0.001..0.01));
-                entropy_heap.push(EntropyPosition {
-                    entropy: cell.entropy(),
-                    pos,
-                })
+        WFC::with_rng(
+            width,
+            height,
+            constraints,
+            distributions,
+            StdRng::seed_from_u64(seed),
+        )
+    }
+    pub fn with_rng(
+        width: usize,
+        height: usize,
+        constraints: Constraints,
+        distributions: Vec<usize>,
+        rng: StdRng,
+    ) -> Self {
+        let tile_count = distributions.len();
+        let mut propagator: Vec<Vec<Vec<usize>>> =
+            vec![vec![Vec::new(); DIRECTIONS.len()]; tile_count];
+        for (tile, rules) in constraints.iter().enumerate().take(tile_count) {
+            for (dir, allowed) in rules {
+                if let Some(d) = DIRECTIONS.iter().position(|d| d == dir) {
+                    propagator[tile][d].extend(allowed.iter().filter(|&&t| t < tile_count));
+                }
             }
         }
-        let uncollapsed_cells = cells.len();
-        WFC {
-            cells,
-            uncollapsed_cells,
-            constraints,
+        let mut supports: Vec<[u32; 4]> = vec![[0; 4]; tile_count];
+        for rules in propagator.iter_mut() {
+            for (d, allowed) in rules.iter_mut().enumerate() {
+                allowed.sort_unstable();
+                allowed.dedup();
+                for &other in allowed.iter() {
+                    supports[other][d] += 1;
+                }
+            }
+        }
+        let weight_log_weights = distributions
+            .iter()
+            .map(|&weight| match weight {
+                0 => 0.,
+                _ => weight as f32 * (weight as f32).log2(),
+            })
+            .collect();
+        let mut wfc = WFC {
+            cells: Grid::new(width, height),
+            uncollapsed_cells: 0,
+            propagator,
+            supports,
+            compatible: Vec::new(),
             distributions,
-            entropy_heap,
+            weight_log_weights,
+            entropy_heap: BinaryHeap::new(),
+            stack: Vec::new(),
+            rng,
+            pins: Vec::new(),
+            connectivity: None,
+            counts: Vec::new(),
+            possible_counts: Vec::new(),
+            decided_counts: Vec::new(),
+            contradiction: None,
+            events: Some(Vec::new()),
+            attempts: 1,
+            max_attempts: 1,
+            failed: false,
+        };
+        wfc.reset();
+        wfc
+    }
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+    pub fn restrict(&mut self, pos: &Position, allowed: &[usize]) -> Result<(), WFCError> {
+        if self.cells.get(pos).is_none() {
+            return Ok(());
         }
+        self.pins.push((*pos, allowed.to_vec()));
+        self.apply_pin(pos, allowed).map_err(|pos| {
+            self.failed = true;
+            WFCError::PinContradiction { pos }
+        })
+    }
+    pub fn pin(&mut self, pos: &Position, tile: usize) -> Result<(), WFCError> {
+        self.restrict(pos, &[tile])
+    }
+    pub fn pin_positions(&mut self, positions: &[Position], tile: usize) -> Result<(), WFCError> {
+        for pos in positions {
+            self.pin(pos, tile)?;
+        }
+        Ok(())
+    }
+    pub fn restrict_border(&mut self, allowed: &[usize]) -> Result<(), WFCError> {
+        let (width, height) = (self.cells.width as i32, self.cells.height as i32);
+        let border: Vec<Position> = self
+            .cells
+            .positions()
+            .filter(|pos| pos.x == 0 || pos.y == 0 || pos.x == width - 1 || pos.y == height - 1)
+            .collect();
+        for pos in border {
+            self.restrict(&pos, allowed)?;
+        }
+        Ok(())
+    }
+    pub fn pin_border(&mut self, tile: usize) -> Result<(), WFCError> {
+        self.restrict_border(&[tile])
+    }
+    pub fn require_connected(&mut self, walkable: &[usize]) {
+        self.connectivity_mut(walkable).all = true;
+    }
+    pub fn require_path(
+        &mut self,
+        walkable: &[usize],
+        positions: &[Position],
+    ) -> Result<(), WFCError> {
+        for pos in positions {
+            self.restrict(pos, walkable)?;
+        }
+        let required: Vec<Position> = positions
+            .iter()
+            .filter(|pos| self.cells.get(pos).is_some())
+            .copied()
+            .collect();
+        self.connectivity_mut(walkable).required.extend(required);
+        Ok(())
+    }
+    pub fn limit_count(&mut self, tile: usize, min: usize, max: usize) {
+        if tile < self.distributions.len() {
+            self.counts.push(TileCount { tile, min, max });
+        }
+    }
+    pub fn limit_ratio(&mut self, tile: usize, min: f32, max: f32) {
+        let cells = self.cells.len() as f32;
+        self.limit_count(
+            tile,
+            (min * cells).ceil() as usize,
+            (max * cells).floor() as usize,
+        );
+    }
+    pub fn run(&mut self) -> Result<(), WFCError> {
+        self.events = None;
+        while let Some(step) = self.advance() {
+            step?;
+        }
+        Ok(())
+    }
+    fn record(&mut self, event: WFCEvent) {
+        if let Some(events) = self.events.as_mut() {
+            events.push(event);
+        }
+    }
+    fn reset(&mut self) {
+        self.entropy_heap.clear();
+        self.stack.clear();
+        self.contradiction = None;
+        let tile_count = self.distributions.len();
+        let total_weight = self.distributions.iter().sum::<usize>() as f32;
+        let total_weight_log_weight = self.weight_log_weights.iter().sum();
+        let (width, height) = (self.cells.width, self.cells.height);
+        self.cells = Grid::new(width, height);
+        for pos in self.cells.positions() {
+            let cell = Cell::new(
+                Bitset::full(tile_count),
+                total_weight,
+                total_weight_log_weight,
+                self.rng.gen_range(0.001..0.01),
+            );
+            self.entropy_heap.push(EntropyPosition {
+                entropy: cell.entropy(),
+                pos,
+            });
+            *self.cells.get_mut(&pos).unwrap() = cell;
+        }
+        self.uncollapsed_cells = self.cells.len();
+        self.possible_counts = vec![self.cells.len(); tile_count];
+        self.decided_counts = vec![0; tile_count];
+        if tile_count == 1 {
+            self.decided_counts[0] = self.cells.len();
+        }
+        self.compatible = (0..self.cells.len())
+            .flat_map(|_| self.supports.iter().copied())
+            .collect();
+        if let Some(connectivity) = self.connectivity.as_mut() {
+            connectivity.dirty = true;
+        }
+        if let Err(pos) = self.ban_unsupported() {
+            self.contradiction = Some(pos);
+        }
+    }
+    fn ban_unsupported(&mut self) -> Result<(), Position> {
+        for pos in self.cells.positions() {
+            for tile in 0..self.distributions.len() {
+                let unsupported = DIRECTIONS.iter().enumerate().any(|(d, dir)| {
+                    self.supports[tile][d] == 0 && self.cells.get(&(pos - *dir)).is_some()
+                });
+                if unsupported {
+                    self.ban(pos, tile)?;
+                }
+            }
+        }
+        self.propagate()
+    }
+    fn ban(&mut self, pos: Position, tile: usize) -> Result<(), Position> {
+        let index = self.cells.index_of(&pos).unwrap() * self.distributions.len() + tile;
+        self.compatible[index] = [0; 4];
+        let cell = self.cells.get_mut(&pos).unwrap();
+        if !cell.possible.contains(tile) {
+            return Ok(());
+        }
+        cell.remove_index(tile, &self.distributions, &self.weight_log_weights);
+        self.update_connectivity(&pos, tile);
+        let cell = self.cells.get_mut(&pos).unwrap();
+        self.possible_counts[tile] -= 1;
+        if cell.remaining == 1 {
+            if let Some(last) = cell.possible.iter().next() {
+                self.decided_counts[last] += 1;
+            }
+        }
+        self.stack.push((pos, tile));
+        if cell.is_contradiction() {
+            return Err(pos);
+        }
+        let entropy = cell.entropy();
+        self.entropy_heap.push(EntropyPosition { entropy, pos });
+        self.record(WFCEvent::Banned { pos, tile, entropy });
+        Ok(())
+    }
+    fn connectivity_mut(&mut self, walkable: &[usize]) -> &mut Connectivity {
+        let mut tiles = Bitset::new(self.distributions.len());
+        for &tile in walkable {
+            tiles.insert(tile);
+        }
+        let connectivity = self.connectivity.get_or_insert_with(|| Connectivity {
+            walkable: Bitset::new(0),
+            required: Vec::new(),
+            all: false,
+            dirty: true,
+        });
+        connectivity.walkable = tiles;
+        connectivity.dirty = true;
+        connectivity
+    }
+    fn ban_except(&mut self, pos: Position, keep: impl Fn(usize) -> bool) -> Result<(), Position> {
+        let removals: Vec<usize> = self
+            .cells
+            .get(&pos)
+            .unwrap()
+            .possible
+            .iter()
+            .filter(|&tile| !keep(tile))
+            .collect();
+        for tile in removals {
+            self.ban(pos, tile)?;
+        }
+        Ok(())
+    }
+    fn apply_pin(&mut self, pos: &Position, allowed: &[usize]) -> Result<(), Position> {
+        let cell = self.cells.get(pos).unwrap();
+        if let Some(chosen_index) = cell.chosen_index {
+            return match allowed.contains(&chosen_index) {
+                true => Ok(()),
+                false => Err(*pos),
+            };
+        }
+        self.ban_except(*pos, |tile| allowed.contains(&tile))?;
+        self.propagate()?;
+        let cell = self.cells.get_mut(pos).unwrap();
+        if cell.remaining != 1 {
+            return Ok(());
+        }
+        let tile = cell.possible.iter().next().unwrap();
+        cell.chosen_index = Some(tile);
+        self.uncollapsed_cells -= 1;
+        self.record(WFCEvent::Collapsed { pos: *pos, tile });
+        Ok(())
+    }
+    fn restart(&mut self) -> Result<(), Position> {
+        self.reset();
+        if self.contradiction.is_some() {
+            return Ok(());
+        }
+        let pins = std::mem::take(&mut self.pins);
+        let result = pins
+            .iter()
+            .try_for_each(|(pos, allowed)| self.apply_pin(pos, allowed));
+        self.pins = pins;
+        result
     }
     fn choose(&mut self) -> Position {
         while let Some(EntropyPosition { entropy: _, pos }) = self.entropy_heap.pop() {
@@ -165,64 +501,243 @@ impl WFC {
         }
         unreachable!("entropy heap is empty");
     }
-    fn collapse(&mut self, pos: &Position) {
+    fn collapse(&mut self, pos: &Position) -> Result<(), Position> {
         let cell = self.cells.get_mut(pos).unwrap();
-        if let Some(chosen_index) = cell.choose_possible(&self.distributions) {
-            cell.collapse(chosen_index, &self.distributions);
-        } else {
-            panic!("cannot collapse cell at {:?}", pos);
-        }
+        let chosen_index = cell
+            .choose_possible(&self.distributions, &mut self.rng)
+            .ok_or(*pos)?;
+        cell.chosen_index = Some(chosen_index);
+        self.record(WFCEvent::Collapsed {
+            pos: *pos,
+            tile: chosen_index,
+        });
+        self.ban_except(*pos, |tile| tile == chosen_index)
     }
-    fn propagate(&mut self, pos: Position) {
-        let mut stack = vec![pos];
-        while let Some(pos) = stack.pop() {
-            for dir in DIRECTIONS {
-                let current_cel = self.cells.get(&pos).unwrap();
-                let mut possible_neighbors: Vec<usize> = Vec::new();
-                for (i, p) in current_cel.possible.iter().enumerate() {
-                    if !p {
+    fn propagate(&mut self) -> Result<(), Position> {
+        let tile_count = self.distributions.len();
+        let mut banned: Vec<usize> = Vec::new();
+        while let Some((pos, tile)) = self.stack.pop() {
+            for (d, dir) in DIRECTIONS.iter().enumerate() {
+                let neighbor = pos + *dir;
+                let offset = match self.cells.index_of(&neighbor) {
+                    Some(index) => index * tile_count,
+                    None => continue,
+                };
+                for &other in self.propagator[tile][d].iter() {
+                    let compatible = &mut self.compatible[offset + other][d];
+                    if *compatible == 0 {
                         continue;
                     }
-                    let constraint = self.constraints.get(i).unwrap();
-                    for (c_dir, allowed_sockets) in constraint {
-                        if *c_dir != *dir {
-                            continue;
-                        }
-                        possible_neighbors.extend(allowed_sockets);
+                    *compatible -= 1;
+                    if *compatible == 0 {
+                        banned.push(other);
                     }
                 }
-                let neighbor = pos + *dir;
-                let neighbor_cell_option = self.cells.get_mut(&neighbor);
-                if neighbor_cell_option.is_none() {
-                    continue;
+                for other in banned.drain(..) {
+                    self.ban(neighbor, other)?;
                 }
-                let neighbor_cell = neighbor_cell_option.unwrap();
-                if neighbor_cell.chosen_index.is_some() {
-                    continue;
+            }
+        }
+        Ok(())
+    }
+    fn enforce_counts(&mut self, pos: Position) -> Result<bool, Position> {
+        let mut changed = false;
+        for TileCount { tile, min, max } in self.counts.clone() {
+            if self.decided_counts[tile] > max || self.possible_counts[tile] < min {
+                return Err(pos);
+            }
+            let ban_rest = self.decided_counts[tile] == max && self.possible_counts[tile] > max;
+            let force_rest = self.possible_counts[tile] == min && self.decided_counts[tile] < min;
+            if !ban_rest && !force_rest {
+                continue;
+            }
+            let undecided: Vec<Position> = self
+                .cells
+                .positions()
+                .filter(|pos| {
+                    let cell = self.cells.get(pos).unwrap();
+                    cell.remaining > 1 && cell.possible.contains(tile)
+                })
+                .collect();
+            for pos in undecided {
+                match ban_rest {
+                    true => self.ban(pos, tile)?,
+                    false => self.ban_except(pos, |other| other == tile)?,
                 }
-                let indexes: Vec<usize> = neighbor_cell
-                    .possible
-                    .iter()
-                    .enumerate()
-                    .filter(|(_, &s)| s)
-                    .map(|(i, _)| i)
-                    .collect();
-
-                for index in indexes {
-                    if !possible_neighbors.contains(&index) {
-                        neighbor_cell.remove_index(index, &self.distributions);
-                        self.entropy_heap.push(EntropyPosition {
-                            entropy: neighbor_cell.entropy(),
-                            pos: neighbor,
-                        });
-                        if !stack.contains(&neighbor) {
-                            stack.push(neighbor);
-                        }
-                    }
+                changed = true;
+            }
+        }
+        Ok(changed)
+    }
+    // A cell that becomes certainly walkable next to another such cell cannot add cuts, so the
+    // full analysis only reruns after a cell closes or a walkable cell appears on its own.
+    fn update_connectivity(&mut self, pos: &Position, tile: usize) {
+        let connectivity = match &self.connectivity {
+            Some(connectivity) => connectivity,
+            None => return,
+        };
+        let possible = &self.cells.get(pos).unwrap().possible;
+        let walkable = connectivity.walkable.contains(tile);
+        let closed = walkable && !possible.intersects(&connectivity.walkable);
+        let opened = !walkable && possible.is_subset(&connectivity.walkable);
+        let dirty = closed || (opened && !self.touches_walkable(pos));
+        if let Some(connectivity) = self.connectivity.as_mut() {
+            connectivity.dirty |= dirty;
+        }
+    }
+    fn touches_walkable(&self, pos: &Position) -> bool {
+        let connectivity = match &self.connectivity {
+            Some(connectivity) if connectivity.all => connectivity,
+            Some(_) => return true,
+            None => return false,
+        };
+        DIRECTIONS.iter().any(|dir| {
+            self.cells
+                .get(&(*pos + *dir))
+                .is_some_and(|cell| cell.possible.is_subset(&connectivity.walkable))
+        })
+    }
+    fn enforce_connectivity(&mut self) -> Result<bool, Position> {
+        let ((cuts, isolated), walkable) = match &self.connectivity {
+            Some(connectivity) if connectivity.dirty => (
+                self.connectivity_cuts(connectivity)?,
+                connectivity.walkable.clone(),
+            ),
+            _ => return Ok(false),
+        };
+        if let Some(connectivity) = self.connectivity.as_mut() {
+            connectivity.dirty = false;
+        }
+        for pos in cuts.iter() {
+            self.ban_except(*pos, |tile| walkable.contains(tile))?;
+        }
+        for pos in isolated.iter() {
+            self.ban_except(*pos, |tile| !walkable.contains(tile))?;
+        }
+        Ok(!cuts.is_empty() || !isolated.is_empty())
+    }
+    fn connectivity_cuts(
+        &self,
+        connectivity: &Connectivity,
+    ) -> Result<(Vec<Position>, Vec<Position>), Position> {
+        let width = self.cells.width;
+        let to_pos = |index: usize| Position::new((index % width) as i32, (index / width) as i32);
+        let open: Vec<bool> = self
+            .cells
+            .iter()
+            .map(|cell| cell.possible.intersects(&connectivity.walkable))
+            .collect();
+        let definite: Vec<bool> = self
+            .cells
+            .iter()
+            .map(|cell| cell.possible.is_subset(&connectivity.walkable))
+            .collect();
+        let mut relevant: Vec<bool> = definite.iter().map(|&d| d && connectivity.all).collect();
+        for pos in connectivity.required.iter() {
+            relevant[self.cells.index_of(pos).unwrap()] = true;
+        }
+        let total = relevant.iter().filter(|&&r| r).count();
+        let root = match relevant.iter().position(|&r| r) {
+            Some(root) if open[root] => root,
+            Some(root) => return Err(to_pos(root)),
+            None => return Ok((Vec::new(), Vec::new())),
+        };
+        let mut discovered = vec![0; self.cells.len()];
+        let mut low = vec![0; self.cells.len()];
+        let mut below = vec![0; self.cells.len()];
+        let mut cuts: Vec<usize> = Vec::new();
+        let mut time = 1;
+        discovered[root] = time;
+        low[root] = time;
+        below[root] = relevant[root] as usize;
+        let mut stack = vec![(root, 0)];
+        while let Some(&(v, d)) = stack.last() {
+            if d < DIRECTIONS.len() {
+                stack.last_mut().unwrap().1 += 1;
+                let w = match self.cells.index_of(&(to_pos(v) + DIRECTIONS[d])) {
+                    Some(w) if open[w] => w,
+                    _ => continue,
+                };
+                if discovered[w] == 0 {
+                    time += 1;
+                    discovered[w] = time;
+                    low[w] = time;
+                    below[w] = relevant[w] as usize;
+                    stack.push((w, 0));
+                } else {
+                    low[v] = low[v].min(discovered[w]);
                 }
+                continue;
             }
+            stack.pop();
+            if let Some(&(u, _)) = stack.last() {
+                low[u] = low[u].min(low[v]);
+                below[u] += below[v];
+                let outside = total - below[v] - relevant[u] as usize;
+                if low[v] >= discovered[u] && below[v] > 0 && outside > 0 && !definite[u] {
+                    cuts.push(u);
+                }
+            }
+        }
+        if let Some(index) = (0..relevant.len()).find(|&i| relevant[i] && discovered[i] == 0) {
+            return Err(to_pos(index));
+        }
+        cuts.sort_unstable();
+        cuts.dedup();
+        let isolated = match connectivity.all {
+            true => (0..open.len())
+                .filter(|&i| open[i] && discovered[i] == 0)
+                .map(to_pos)
+                .collect(),
+            false => Vec::new(),
+        };
+        Ok((cuts.into_iter().map(to_pos).collect(), isolated))
+    }
+    fn enforce_global(&mut self, pos: Position) -> Result<(), Position> {
+        loop {
+            let counted = self.enforce_counts(pos)?;
+            let connected = self.enforce_connectivity()?;
+            if !counted && !connected {
+                return Ok(());
+            }
+            self.propagate()?;
         }
     }
+    fn advance(&mut self) -> Option<Result<(), WFCError>> {
+        if self.failed || self.uncollapsed_cells == 0 {
+            return None;
+        }
+        if let Err(pos) = self.step() {
+            if self.attempts >= self.max_attempts {
+                self.failed = true;
+                return Some(Err(WFCError::Contradiction {
+                    pos,
+                    attempts: self.attempts,
+                }));
+            }
+            self.attempts += 1;
+            self.record(WFCEvent::Restarted {
+                pos,
+                attempts: self.attempts,
+            });
+            if let Err(pos) = self.restart() {
+                self.failed = true;
+                return Some(Err(WFCError::PinContradiction { pos }));
+            }
+        }
+        Some(Ok(()))
+    }
+    fn step(&mut self) -> Result<(), Position> {
+        if let Some(pos) = self.contradiction {
+            return Err(pos);
+        }
+        let next_pos = self.choose();
+        self.collapse(&next_pos)?;
+        self.propagate()?;
+        self.enforce_global(next_pos)?;
+        self.uncollapsed_cells -= 1;
+        Ok(())
+    }
 }
 
 impl std::fmt::Display for WFC {
@@ -230,14 +745,7 @@ impl std::fmt::Display for WFC {
         for y in 0..self.cells.height {
             for x in 0..self.cells.width {
                 let cell = self.cells.get(&Position::new(x as i32, y as i32)).unwrap();
-                let possible: Vec<usize> = cell
-                    .possible
-                    .iter()
-                    .enumerate()
-                    .filter(|(_i, &p)| p)
-                    .map(|(i, _p)| i)
-                    .collect();
-                let s = String::from_iter(possible.iter().map(|&i| i.to_string()));
+                let s = String::from_iter(cell.possible.iter().map(|i| i.to_string()));
                 write!(f, "{s:03} ")?;
             }
             write!(f, "\n")?;
@@ -247,26 +755,21 @@ impl std::fmt::Display for WFC {
 }
 
 impl Iterator for WFC {
-    type Item = ();
-    fn next(&mut self) -> Option<()> {
-        if self.uncollapsed_cells == 0 {
-            return None;
-        }
-        let next_pos = self.choose();
-        self.collapse(&next_pos);
-        self.propagate(next_pos);
-        self.uncollapsed_cells -= 1;
-        Some(())
+    type Item = Result<Vec<WFCEvent>, WFCError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let step = self.advance();
+        let events = self.events.replace(Vec::new()).unwrap_or_default();
+        step.map(|step| step.map(|_| events))
     }
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use std::time::{Duration, Instant};
 
-    #[test]
-    fn test_basic_wfc_tiled() {
-        let constraints: Vec<Vec<(Position, Vec<usize>)>> = vec![
+    fn constraints() -> Constraints {
+        vec![
             vec![
                 (Position::UP, vec![0, 1]),
                 (Position::DOWN, vec![0, 1]),
@@ -285,11 +788,256 @@ pub mod tests {
                 (Position::LEFT, vec![1, 2]),
                 (Position::RIGHT, vec![1, 2]),
             ],
-        ];
-        let mut wfc = WFC::new(5, 5, constraints, vec![1, 1, 1]);
+        ]
+    }
+
+    fn chosen(wfc: &WFC) -> Vec<Option<usize>> {
+        wfc.cells.iter().map(|cell| cell.chosen_index).collect()
+    }
+
+    #[test]
+    fn test_basic_wfc_tiled() {
+        let mut wfc = WFC::new(5, 5, constraints(), vec![1, 1, 1], 0);
         while wfc.next().is_some() {
             println!("{}", wfc);
         }
         assert!(wfc.next().is_none());
     }
+
+    #[test]
+    fn test_seeded_wfc_is_reproducible() {
+        let mut a = WFC::new(10, 10, constraints(), vec![1, 1, 1], 1234);
+        let mut b = WFC::new(10, 10, constraints(), vec![1, 1, 1], 1234);
+        a.run().unwrap();
+        b.run().unwrap();
+        assert_eq!(chosen(&a), chosen(&b));
+        assert!(chosen(&a).iter().all(Option::is_some));
+        let mut c = WFC::new(10, 10, constraints(), vec![1, 1, 1], 4321);
+        c.run().unwrap();
+        assert_ne!(chosen(&a), chosen(&c));
+    }
+
+    #[test]
+    fn test_lowest_entropy_first() {
+        let mut heap: BinaryHeap<EntropyPosition> = [2.5, 0.5, 1.5]
+            .iter()
+            .enumerate()
+            .map(|(x, &entropy)| EntropyPosition {
+                entropy,
+                pos: Position::new(x as i32, 0),
+            })
+            .collect();
+        assert_eq!(heap.pop().map(|e| e.pos), Some(Position::new(1, 0)));
+        assert_eq!(heap.pop().map(|e| e.pos), Some(Position::new(2, 0)));
+    }
+
+    #[test]
+    fn test_large_wfc() {
+        let tile_count = 24;
+        let rules: Constraints = (0..tile_count)
+            .map(|tile| {
+                let allowed: Vec<usize> = (0..tile_count)
+                    .filter(|&other| (tile as i32 - other as i32).abs() <= 2)
+                    .collect();
+                DIRECTIONS
+                    .iter()
+                    .map(|&dir| (dir, allowed.clone()))
+                    .collect()
+            })
+            .collect();
+        let budget = match cfg!(debug_assertions) {
+            true => Duration::from_secs(15),
+            false => Duration::from_millis(500),
+        };
+        let start = Instant::now();
+        let mut wfc = WFC::new(256, 256, rules, vec![1; tile_count], 7);
+        wfc.run().unwrap();
+        assert!(start.elapsed() < budget, "{:?}", start.elapsed());
+        for pos in wfc.cells.positions() {
+            let tile = wfc.cells.get(&pos).unwrap().chosen_index.unwrap();
+            for dir in DIRECTIONS {
+                if let Some(neighbor) = wfc.cells.get(&(pos + *dir)) {
+                    assert!(tile.abs_diff(neighbor.chosen_index.unwrap()) <= 2);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_unsatisfiable_wfc() {
+        let constraints = vec![DIRECTIONS.iter().map(|&dir| (dir, vec![])).collect()];
+        let mut wfc = WFC::new(2, 1, constraints, vec![1], 0).with_max_attempts(3);
+        assert!(matches!(
+            wfc.run(),
+            Err(WFCError::Contradiction { attempts: 3, .. })
+        ));
+        assert!(wfc.next().is_none());
+
+        let constraints = vec![DIRECTIONS.iter().map(|&dir| (dir, vec![])).collect()];
+        let mut wfc = WFC::new(2, 1, constraints, vec![1], 0).with_max_attempts(2);
+        let events = wfc.next().unwrap().unwrap();
+        assert!(matches!(events[0], WFCEvent::Restarted { attempts: 2, .. }));
+        assert!(wfc.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_wfc_events() {
+        let mut wfc = WFC::new(6, 6, constraints(), vec![1, 1, 1], 11);
+        let mut tiles: Grid<Option<usize>> = Grid::new(6, 6);
+        let mut bans = 0;
+        for step in wfc.by_ref() {
+            for event in step.unwrap() {
+                match event {
+                    WFCEvent::Collapsed { pos, tile } => *tiles.get_mut(&pos).unwrap() = Some(tile),
+                    WFCEvent::Banned { entropy, .. } => {
+                        assert!(entropy.is_finite());
+                        bans += 1;
+                    }
+                    WFCEvent::Restarted { .. } => tiles = Grid::new(6, 6),
+                }
+            }
+        }
+        assert!(bans > 0);
+        assert_eq!(tiles.iter().copied().collect::<Vec<_>>(), chosen(&wfc));
+    }
+
+    #[test]
+    fn test_infer_constraints() {
+        let mut example: Grid<usize> = Grid::new(3, 2);
+        for (pos, tile) in example.positions().zip([0, 1, 2, 0, 1, 1]) {
+            *example.get_mut(&pos).unwrap() = tile;
+        }
+        let (rules, distributions) = infer_constraints(&example);
+        assert_eq!(distributions, vec![2, 3, 1]);
+        let allowed = |tile: usize, dir: Position| {
+            rules[tile]
+                .iter()
+                .find(|(d, _)| *d == dir)
+                .map(|(_, allowed)| allowed.clone())
+                .unwrap()
+        };
+        assert_eq!(allowed(0, Position::RIGHT), vec![1]);
+        assert_eq!(allowed(1, Position::LEFT), vec![0, 1]);
+        assert_eq!(allowed(2, Position::UP), vec![1]);
+        assert!(allowed(0, Position::LEFT).is_empty());
+
+        let mut wfc = WFC::new(6, 6, constraints(), vec![1, 1, 1], 7);
+        wfc.run().unwrap();
+        let chosen: Grid<usize> = wfc.cells.map(|cell| cell.chosen_index.unwrap());
+        let (inferred, _) = infer_constraints(&chosen);
+        for (dir, allowed) in &inferred[0] {
+            assert!(!allowed.contains(&2), "{:?}", dir);
+        }
+    }
+
+    #[test]
+    fn test_pinned_wfc() {
+        let mut wfc = WFC::new(5, 5, constraints(), vec![1, 1, 1], 3);
+        wfc.pin_border(2).unwrap();
+        wfc.pin(&Position::new(2, 2), 0).unwrap();
+        wfc.restrict(&Position::new(1, 2), &[0, 1]).unwrap();
+        wfc.run().unwrap();
+        for pos in wfc.cells.positions() {
+            let chosen = wfc.cells.get(&pos).unwrap().chosen_index.unwrap();
+            if pos.x == 0 || pos.y == 0 || pos.x == 4 || pos.y == 4 {
+                assert_eq!(chosen, 2);
+            } else if pos == Position::new(2, 2) {
+                assert_eq!(chosen, 0);
+            }
+            for dir in DIRECTIONS {
+                if let Some(neighbor) = wfc.cells.get(&(pos + *dir)) {
+                    assert!(chosen.abs_diff(neighbor.chosen_index.unwrap()) < 2);
+                }
+            }
+        }
+        assert_eq!(
+            wfc.cells.get(&Position::new(1, 2)).unwrap().chosen_index,
+            Some(1)
+        );
+
+        let mut wfc = WFC::new(5, 5, constraints(), vec![1, 1, 1], 3);
+        wfc.pin(&Position::new(0, 0), 0).unwrap();
+        assert_eq!(
+            wfc.pin(&Position::new(1, 0), 2),
+            Err(WFCError::PinContradiction {
+                pos: Position::new(1, 0)
+            })
+        );
+        assert!(wfc.next().is_none());
+    }
+
+    fn floor_rules() -> Vec<Vec<(Position, Vec<usize>)>> {
+        (0..2)
+            .map(|_| DIRECTIONS.iter().map(|&dir| (dir, vec![0, 1])).collect())
+            .collect()
+    }
+
+    fn reachable(wfc: &WFC, from: Position) -> Vec<Position> {
+        let floor =
+            |pos: &Position| wfc.cells.get(pos).and_then(|cell| cell.chosen_index) == Some(0);
+        let mut seen = vec![from];
+        let mut open = vec![from];
+        while let Some(pos) = open.pop() {
+            for dir in DIRECTIONS {
+                let next = pos + *dir;
+                if floor(&next) && !seen.contains(&next) {
+                    seen.push(next);
+                    open.push(next);
+                }
+            }
+        }
+        seen
+    }
+
+    #[test]
+    fn test_count_limits() {
+        let mut wfc = WFC::new(10, 10, constraints(), vec![1, 1, 1], 5);
+        wfc.limit_count(2, 0, 5);
+        wfc.limit_ratio(0, 0.3, 1.);
+        wfc.run().unwrap();
+        let count = |tile| chosen(&wfc).iter().filter(|&&c| c == Some(tile)).count();
+        assert!(count(2) <= 5);
+        assert!(count(0) >= 30);
+    }
+
+    #[test]
+    fn test_connectivity() {
+        for seed in 0..5 {
+            let (from, to) = (Position::new(0, 0), Position::new(11, 11));
+            let mut wfc = WFC::new(12, 12, floor_rules(), vec![1, 3], seed);
+            wfc.require_path(&[0], &[from, to]).unwrap();
+            wfc.run().unwrap();
+            assert!(reachable(&wfc, from).contains(&to));
+
+            let mut wfc = WFC::new(12, 12, floor_rules(), vec![1, 1], seed);
+            wfc.require_connected(&[0]);
+            wfc.run().unwrap();
+            let floors: Vec<Position> = wfc
+                .cells
+                .positions()
+                .filter(|pos| wfc.cells.get(pos).unwrap().chosen_index == Some(0))
+                .collect();
+            assert_eq!(reachable(&wfc, floors[0]).len(), floors.len());
+        }
+    }
+
+    #[test]
+    fn test_large_connectivity() {
+        let budget = match cfg!(debug_assertions) {
+            true => Duration::from_secs(10),
+            false => Duration::from_secs(1),
+        };
+        let start = Instant::now();
+        let mut wfc = WFC::new(48, 48, floor_rules(), vec![1, 1], 3);
+        wfc.require_connected(&[0]);
+        wfc.run().unwrap();
+        assert!(start.elapsed() < budget, "{:?}", start.elapsed());
+        let floors: Vec<Position> = wfc
+            .cells
+            .positions()
+            .filter(|pos| wfc.cells.get(pos).unwrap().chosen_index == Some(0))
+            .collect();
+        assert!(floors.len() > 48 * 48 / 4);
+        assert_eq!(reachable(&wfc, floors[0]).len(), floors.len());
+    }
 }
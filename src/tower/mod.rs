@@ -1,43 +1,110 @@
-use std::time::Duration;
+use std::{env, time::Duration};
 
-use bevy::prelude::*;
+use bevy::{
+    asset::LoadState,
+    ecs::schedule::ShouldRun,
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task},
+};
+use futures_lite::future;
+use rand::prelude::*;
 
 use crate::{
-    assets::TextureHandles,
+    assets::{TextureHandles, TilesetHandles},
+    combat::{Attack, Defense, Health},
     constants::{
-        DIRT_SPRITE_INDEX, FLOOR_SPRITE_INDEX, GRASS_SPRITE_INDEX, PLAYER_SPRITE_INDEX,
-        SPRITE_SCALE, WATER_SPRITE_INDEX,
+        ANIMATE_WFC_ENV_VAR, BSP_LEVEL_SIZE, CAVE_LEVEL_SIZE, DEFAULT_WINDOW_TITLE,
+        DRUNKARD_LEVEL_SIZE, LEVEL_GENERATION_ATTEMPTS, MARSH_LEVEL_SIZE, MARSH_SAMPLE,
+        OVERWORLD_BORDER_TILE, OVERWORLD_ENTRANCE_TILE, OVERWORLD_SIZE, OVERWORLD_TILESET_PATH,
+        PLAYER_ATTACK, PLAYER_DEFENSE, PLAYER_HEALTH, PLAYER_SPEED, PLAYER_SPRITE_INDEX,
+        RUINS_LEVEL_SIZE, RUINS_SAMPLE, SAVE_FILE_PATH, SEED_ENV_VAR, SPRITE_SCALE,
+        TUNNELER_LEVEL_SIZE, WFC_MAX_ATTEMPTS, WFC_MAX_UNWALKABLE_RATIO,
     },
+    hostiles::{restore_hostiles, PopulatedMaps},
     player::Player,
     position::Position,
+    save,
+    turn::Actor,
 };
 
-use self::map_gen::wfc::WFC;
-use self::tower::Tower;
+use self::grid::Grid;
+use self::map_gen::bsp::BspGenerator;
+use self::map_gen::cave::CaveGenerator;
+use self::map_gen::drunkard::DrunkardGenerator;
+use self::map_gen::overlapping::{sample_from_str, OverlappingModel, OverlappingOptions};
+use self::map_gen::tileset::{Tileset, TilesetLoader};
+use self::map_gen::tunneler::TunnelerGenerator;
+use self::map_gen::wfc::{WFCError, WFCEvent, WFC};
+use self::map_gen::MapGenerator;
+use self::tile::Terrain;
+use self::tower::{Location, MapId, Tower};
 
-mod grid;
-mod map_gen;
-mod tile;
+pub mod grid;
+pub mod legend;
+pub mod map_gen;
+pub mod path;
+pub mod tile;
 pub mod tower;
 
 pub struct TowerPlugin;
 
 impl Plugin for TowerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system_to_stage(StartupStage::PostStartup, spawn_player_system)
+        app.insert_resource(GameState::Loading)
+            .add_asset::<Tileset>()
+            .init_asset_loader::<TilesetLoader>()
             .add_startup_system_to_stage(StartupStage::PostStartup, spawn_tower_system)
-            .add_system(update_sprite_index_system);
+            .add_system(update_current_map_system)
+            .add_system(change_map_system)
+            .add_system(start_wave_system)
+            .add_system(finish_generation_system)
+            .add_system(finish_levels_system)
+            .add_system(update_game_state_system)
+            .add_system(update_sprite_index_system)
+            .add_system(update_title_system);
     }
 }
 
+pub struct Seed(pub u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameState {
+    Loading,
+    Playing,
+}
+
+pub fn run_if_playing(state: Res<GameState>) -> ShouldRun {
+    match *state {
+        GameState::Loading => ShouldRun::No,
+        GameState::Playing => ShouldRun::Yes,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CurrentMap {
+    pub map: MapId,
+}
+
+#[derive(Debug, Component, Clone, Copy, PartialEq, Eq)]
+pub struct OnMap(pub MapId);
+
 #[derive(Component)]
 pub struct Tile;
 
+#[derive(Component)]
+pub struct BlocksMovement;
+
 #[derive(Component)]
 pub struct Explorable;
 
-fn spawn_player_system(mut commands: Commands, texture_handles: Res<TextureHandles>) {
-    commands
+fn spawn_player(
+    commands: &mut Commands,
+    texture_handles: &TextureHandles,
+    tower: &mut Tower,
+    location: Location,
+    health: Health,
+) {
+    let entity = commands
         .spawn_bundle(SpriteSheetBundle {
             sprite: TextureAtlasSprite {
                 index: PLAYER_SPRITE_INDEX,
@@ -51,52 +118,197 @@ fn spawn_player_system(mut commands: Commands, texture_handles: Res<TextureHandl
             },
             ..default()
         })
-        .insert(Position::new(10, 10))
-        .insert(Player);
+        .insert(location.pos)
+        .insert(OnMap(location.map))
+        .insert(BlocksMovement)
+        .insert(Name::new("player"))
+        .insert(Actor::new(PLAYER_SPEED))
+        .insert(health)
+        .insert(Attack(PLAYER_ATTACK))
+        .insert(Defense(PLAYER_DEFENSE))
+        .insert(Player)
+        .id();
+    tower.insert_entity(entity, &location);
 }
 
 fn spawn_tower_system(mut commands: Commands, texture_handles: Res<TextureHandles>) {
-    let width = 20;
-    let height = 20;
-    let mut tower = Tower::new();
-    let level_name = "level1";
-    let map_name = "map1";
-    tower.insert_empty_map(level_name, map_name, width, height);
-    let constraints: Vec<Vec<(Position, Vec<usize>)>> = vec![
-        vec![
-            (Position::UP, vec![0, 1]),
-            (Position::DOWN, vec![0, 1]),
-            (Position::LEFT, vec![0, 1]),
-            (Position::RIGHT, vec![0, 1]),
-        ],
-        vec![
-            (Position::UP, vec![0, 1, 2]),
-            (Position::DOWN, vec![0, 1, 2]),
-            (Position::LEFT, vec![0, 1, 2]),
-            (Position::RIGHT, vec![0, 1, 2]),
-        ],
-        vec![
-            (Position::UP, vec![1, 2]),
-            (Position::DOWN, vec![1, 2]),
-            (Position::LEFT, vec![1, 2]),
-            (Position::RIGHT, vec![1, 2]),
-        ],
+    let loaded = match save::load(SAVE_FILE_PATH) {
+        Ok(save_game) => save_game.player().map(|player| (save_game, player)),
+        Err(e) => {
+            if !e.is_missing() {
+                warn!("could not load save, starting a new tower: {}", e);
+            }
+            None
+        }
+    };
+    let (mut tower, player, health) = match loaded {
+        Some((save_game, player)) => {
+            commands.insert_resource(GameState::Playing);
+            commands.insert_resource(Seed(save_game.seed));
+            let mut tower = save_game.tower;
+            restore_hostiles(
+                &mut commands,
+                &texture_handles,
+                &mut tower,
+                &save_game.entities,
+            );
+            commands.insert_resource(PopulatedMaps(save_game.populated.into_iter().collect()));
+            (tower, player.location, player.health)
+        }
+        None => {
+            let seed = generation_seed();
+            info!("generating tower with seed {}", seed);
+            commands.insert_resource(Seed(seed));
+            let (tower, player) = generate_tower(&mut commands, seed);
+            (tower, player, Health::new(PLAYER_HEALTH))
+        }
+    };
+    spawn_player(&mut commands, &texture_handles, &mut tower, player, health);
+    commands.insert_resource(tower);
+    commands.insert_resource(CurrentMap { map: player.map });
+}
+
+fn generation_seed() -> u64 {
+    env::var(SEED_ENV_VAR)
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(|| thread_rng().gen())
+}
+
+fn walkable_positions(grid: &Grid<tile::Tile>) -> Vec<Position> {
+    grid.positions()
+        .filter(|pos| grid.get(pos).unwrap().walkable())
+        .collect()
+}
+
+fn generate_level(
+    generator: &dyn MapGenerator,
+    (width, height): (usize, usize),
+    rng: &mut StdRng,
+) -> Option<GeneratedLevel> {
+    let grid = generator.generate(width, height, rng)?;
+    let spots: Vec<Position> = walkable_positions(&grid)
+        .choose_multiple(rng, 2)
+        .copied()
+        .collect();
+    match spots[..] {
+        [landing, stairs] => Some(GeneratedLevel {
+            grid,
+            landing,
+            stairs,
+        }),
+        _ => None,
+    }
+}
+
+fn generate_levels(seed: u64) -> Vec<GeneratedLevel> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let ruins = OverlappingModel::new(
+        &sample_from_str(RUINS_SAMPLE),
+        OverlappingOptions {
+            rotations: true,
+            reflections: true,
+            ..OverlappingOptions::default()
+        },
+    );
+    let marsh = Tileset::from_example(&sample_from_str(MARSH_SAMPLE));
+    let generators: Vec<(Box<dyn MapGenerator>, (usize, usize))> = vec![
+        (Box::new(BspGenerator::default()), BSP_LEVEL_SIZE),
+        (Box::new(CaveGenerator::default()), CAVE_LEVEL_SIZE),
+        (Box::new(DrunkardGenerator::default()), DRUNKARD_LEVEL_SIZE),
+        (Box::new(TunnelerGenerator::default()), TUNNELER_LEVEL_SIZE),
+        (Box::new(ruins), RUINS_LEVEL_SIZE),
+        (Box::new(marsh), MARSH_LEVEL_SIZE),
     ];
-    let wfc = WFC::new(width, height, constraints, vec![1, 1, 1]);
-    commands.insert_resource(wfc);
-    let wave_timer = WaveTimer {
-        timer: Timer::new(Duration::from_millis(50), true),
+    generators
+        .iter()
+        .filter_map(|(generator, size)| {
+            let level = (0..LEVEL_GENERATION_ATTEMPTS)
+                .find_map(|_| generate_level(generator.as_ref(), *size, &mut rng));
+            if level.is_none() {
+                warn!(
+                    "could not generate a {}x{} level, skipping it",
+                    size.0, size.1
+                );
+            }
+            level
+        })
+        .collect()
+}
+
+fn generate_tower(commands: &mut Commands, seed: u64) -> (Tower, Location) {
+    let (width, height) = OVERWORLD_SIZE;
+    let mut tower = Tower::new();
+    let level1 = tower.insert_level("level1");
+    let map1 = tower
+        .insert_empty_map(level1, "map1", width, height)
+        .unwrap();
+    let stairs = Position::new(15, 15);
+    let start = Position::new(10, 10);
+    commands.insert_resource(PendingWave {
+        map: map1,
+        seed,
+        walkable: vec![stairs, start],
+    });
+    let task = AsyncComputeTaskPool::get().spawn(async move { generate_levels(seed) });
+    commands.insert_resource(LevelGeneration {
+        stairs: Location::new(map1, stairs),
+        task,
+    });
+    (tower, Location::new(map1, start))
+}
+
+fn update_title_system(seed: Option<Res<Seed>>, mut windows: ResMut<Windows>) {
+    let seed = match seed {
+        Some(seed) if seed.is_changed() => seed,
+        _ => return,
     };
-    commands.insert_resource(wave_timer);
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_title(format!("{} - seed {}", DEFAULT_WINDOW_TITLE, seed.0));
+    }
+}
+
+fn update_current_map_system(
+    player_query: Query<&OnMap, (With<Player>, Changed<OnMap>)>,
+    mut current_map: ResMut<CurrentMap>,
+) {
+    if let Ok(on_map) = player_query.get_single() {
+        if current_map.map != on_map.0 {
+            current_map.map = on_map.0;
+        }
+    }
+}
+
+fn change_map_system(
+    mut commands: Commands,
+    current_map: Res<CurrentMap>,
+    texture_handles: Res<TextureHandles>,
+    mut tower: ResMut<Tower>,
+    mut loaded_map: Local<Option<MapId>>,
+    tile_query: Query<(Entity, &Position, &OnMap), With<Tile>>,
+) {
+    if *loaded_map == Some(current_map.map) {
+        return;
+    }
+    for (entity, pos, on_map) in tile_query.iter() {
+        commands.entity(entity).despawn();
+        if let Some(tile) = tower.get_tile_mut(on_map.0, pos) {
+            tile.entities.retain(|&e| e != entity);
+        }
+    }
+    let (width, height) = tower
+        .get_dimensions(current_map.map)
+        .expect("Error: could not find current map");
     for y in 0..height {
         for x in 0..width {
             let pos = Position {
                 x: x as i32,
                 y: y as i32,
             };
+            let tile = tower.get_tile_mut(current_map.map, &pos).unwrap();
             let mut ec = commands.spawn_bundle(SpriteSheetBundle {
                 sprite: TextureAtlasSprite {
-                    index: FLOOR_SPRITE_INDEX,
+                    index: tile.sprite_index(),
                     ..default()
                 },
                 texture_atlas: texture_handles.atlas.clone(),
@@ -107,47 +319,303 @@ fn spawn_tower_system(mut commands: Commands, texture_handles: Res<TextureHandle
                 visibility: Visibility { is_visible: true },
                 ..default()
             });
-            if let Some(tile) = tower.get_tile_mut(level_name, map_name, &pos) {
-                tile.entities.push(ec.id())
-            }
+            tile.entities.push(ec.id());
             ec.insert(pos);
+            ec.insert(OnMap(current_map.map));
             ec.insert(Explorable);
             ec.insert(Tile);
         }
     }
-    commands.insert_resource(tower);
+    *loaded_map = Some(current_map.map);
 }
 
-struct WaveTimer {
+struct PendingWave {
+    map: MapId,
+    seed: u64,
+    walkable: Vec<Position>,
+}
+
+struct Wave {
+    map: MapId,
+    terrains: Vec<Terrain>,
     timer: Timer,
 }
 
+struct Generation {
+    map: MapId,
+    terrains: Vec<Terrain>,
+    task: Task<Result<WFC, WFCError>>,
+}
+
+struct GeneratedLevel {
+    grid: Grid<tile::Tile>,
+    landing: Position,
+    stairs: Position,
+}
+
+struct LevelGeneration {
+    stairs: Location,
+    task: Task<Vec<GeneratedLevel>>,
+}
+
+fn animate_generation() -> bool {
+    env::var(ANIMATE_WFC_ENV_VAR).is_ok_and(|value| value != "0")
+}
+
+fn apply_wave(wfc: &WFC, map: MapId, terrains: &[Terrain], tower: &mut Tower) {
+    for pos in wfc.cells.positions() {
+        let terrain = match wfc.cells.get(&pos).unwrap().chosen_index {
+            Some(index) => terrains[index],
+            None => continue,
+        };
+        if let Some(tile) = tower.get_tile_mut(map, &pos) {
+            if tile.terrain != terrain {
+                tile.set_terrain(terrain);
+            }
+        }
+    }
+}
+
+fn set_terrain(
+    tower: &mut Tower,
+    query: &mut Query<&mut TextureAtlasSprite, With<Tile>>,
+    map: MapId,
+    pos: &Position,
+    terrain: Terrain,
+) {
+    let tile = match tower.get_tile_mut(map, pos) {
+        Some(tile) => tile,
+        None => return,
+    };
+    if tile.terrain != terrain {
+        tile.set_terrain(terrain);
+    }
+    for &entity in tile.entities.iter() {
+        if let Ok(mut sprite) = query.get_mut(entity) {
+            sprite.index = tile.sprite_index();
+        }
+    }
+}
+
+fn update_sprites(
+    query: &mut Query<(&mut TextureAtlasSprite, &Position, &OnMap), With<Tile>>,
+    map: MapId,
+    tower: &Tower,
+) {
+    for (mut sprite, pos, on_map) in query.iter_mut() {
+        if on_map.0 != map {
+            continue;
+        }
+        sprite.index = tower.get_tile(map, pos).unwrap().sprite_index();
+    }
+}
+
+fn prepare_wfc(pending: &PendingWave, tileset: &Tileset, tower: &Tower) -> Option<WFC> {
+    let issues = tileset.validate();
+    for issue in issues.iter() {
+        match issue.is_error() {
+            true => error!("invalid tileset: {}", issue),
+            false => warn!("tileset: {}", issue),
+        }
+    }
+    if issues.iter().any(|issue| issue.is_error()) {
+        return None;
+    }
+    let (width, height) = tower.get_dimensions(pending.map)?;
+    let mut wfc = WFC::new(
+        width,
+        height,
+        tileset.constraints(),
+        tileset.distributions(),
+        pending.seed,
+    )
+    .with_max_attempts(WFC_MAX_ATTEMPTS);
+    let walkable: Vec<usize> = tileset
+        .terrains()
+        .iter()
+        .enumerate()
+        .filter(|(_, terrain)| terrain.walkable())
+        .map(|(i, _)| i)
+        .collect();
+    for (tile, terrain) in tileset.terrains().iter().enumerate() {
+        if !terrain.walkable() {
+            wfc.limit_ratio(tile, 0., WFC_MAX_UNWALKABLE_RATIO);
+        }
+    }
+    let border = tileset.index(OVERWORLD_BORDER_TILE);
+    let entrance = tileset.index(OVERWORLD_ENTRANCE_TILE);
+    let pinned = match (border, entrance) {
+        (Some(border), Some(entrance)) => wfc
+            .pin_border(border)
+            .and_then(|_| wfc.pin_positions(&pending.walkable, entrance)),
+        _ => {
+            warn!(
+                "tileset has no {} or {} tile, skipping pins",
+                OVERWORLD_BORDER_TILE, OVERWORLD_ENTRANCE_TILE
+            );
+            Ok(())
+        }
+    };
+    wfc.require_connected(&walkable);
+    if let Err(e) = pinned.and_then(|_| wfc.require_path(&walkable, &pending.walkable)) {
+        error!("could not generate map: {}", e);
+        return None;
+    }
+    Some(wfc)
+}
+
+fn start_wave_system(
+    mut commands: Commands,
+    pending: Option<Res<PendingWave>>,
+    asset_server: Res<AssetServer>,
+    tileset_handles: Res<TilesetHandles>,
+    tilesets: Res<Assets<Tileset>>,
+    tower: Res<Tower>,
+) {
+    let pending = match pending {
+        Some(pending) => pending,
+        None => return,
+    };
+    let tileset = match tilesets.get(&tileset_handles.overworld) {
+        Some(tileset) => tileset,
+        None => {
+            if asset_server.get_load_state(&tileset_handles.overworld) == LoadState::Failed {
+                error!("could not load tileset {}", OVERWORLD_TILESET_PATH);
+                commands.remove_resource::<PendingWave>();
+            }
+            return;
+        }
+    };
+    commands.remove_resource::<PendingWave>();
+    let mut wfc = match prepare_wfc(&pending, tileset, &tower) {
+        Some(wfc) => wfc,
+        None => return,
+    };
+    if animate_generation() {
+        commands.insert_resource(wfc);
+        commands.insert_resource(Wave {
+            map: pending.map,
+            terrains: tileset.terrains(),
+            timer: Timer::new(Duration::from_millis(50), true),
+        });
+        return;
+    }
+    let task = AsyncComputeTaskPool::get().spawn(async move { wfc.run().map(|_| wfc) });
+    commands.insert_resource(Generation {
+        map: pending.map,
+        terrains: tileset.terrains(),
+        task,
+    });
+}
+
+fn finish_generation_system(
+    mut commands: Commands,
+    generation: Option<ResMut<Generation>>,
+    mut query: Query<(&mut TextureAtlasSprite, &Position, &OnMap), With<Tile>>,
+    mut tower: ResMut<Tower>,
+) {
+    let mut generation = match generation {
+        Some(generation) => generation,
+        None => return,
+    };
+    let result = match future::block_on(future::poll_once(&mut generation.task)) {
+        Some(result) => result,
+        None => return,
+    };
+    commands.remove_resource::<Generation>();
+    match result {
+        Ok(wfc) => {
+            info!("generated map in {} attempts", wfc.attempts());
+            apply_wave(&wfc, generation.map, &generation.terrains, &mut tower);
+            update_sprites(&mut query, generation.map, &tower);
+        }
+        Err(e) => error!("could not generate map: {}", e),
+    }
+}
+
+fn finish_levels_system(
+    mut commands: Commands,
+    generation: Option<ResMut<LevelGeneration>>,
+    mut query: Query<(&mut TextureAtlasSprite, &Position, &OnMap), With<Tile>>,
+    mut tower: ResMut<Tower>,
+) {
+    let mut generation = match generation {
+        Some(generation) => generation,
+        None => return,
+    };
+    let levels = match future::block_on(future::poll_once(&mut generation.task)) {
+        Some(levels) => levels,
+        None => return,
+    };
+    commands.remove_resource::<LevelGeneration>();
+    let mut down = generation.stairs;
+    for (i, level) in levels.into_iter().enumerate() {
+        let id = tower.insert_level(format!("level{}", i + 2));
+        let map = match tower.insert_map_from_grid(id, "map1", level.grid) {
+            Some(map) => map,
+            None => continue,
+        };
+        tower.insert_stairs(down, Location::new(map, level.landing));
+        down = Location::new(map, level.stairs);
+    }
+    update_sprites(&mut query, generation.stairs.map, &tower);
+}
+
+fn update_game_state_system(
+    mut game_state: ResMut<GameState>,
+    pending: Option<Res<PendingWave>>,
+    wave: Option<Res<Wave>>,
+    generation: Option<Res<Generation>>,
+    levels: Option<Res<LevelGeneration>>,
+) {
+    if *game_state == GameState::Loading
+        && pending.is_none()
+        && wave.is_none()
+        && generation.is_none()
+        && levels.is_none()
+    {
+        *game_state = GameState::Playing;
+    }
+}
+
 fn update_sprite_index_system(
-    mut wfc: ResMut<WFC>,
+    mut commands: Commands,
+    wfc: Option<ResMut<WFC>>,
     time: Res<Time>,
-    mut timer: ResMut<WaveTimer>,
-    mut query: Query<(&mut TextureAtlasSprite, &Position), With<Tile>>,
+    wave: Option<ResMut<Wave>>,
+    mut query: Query<&mut TextureAtlasSprite, With<Tile>>,
     mut tower: ResMut<Tower>,
 ) {
-    timer.timer.tick(time.delta());
-    if !timer.timer.just_finished() {
-        return;
-    }
-    if wfc.uncollapsed_cells == 0 {
+    let (mut wfc, mut wave) = match (wfc, wave) {
+        (Some(wfc), Some(wave)) => (wfc, wave),
+        _ => return,
+    };
+    wave.timer.tick(time.delta());
+    if !wave.timer.just_finished() {
         return;
     }
-    wfc.next();
-    for (mut sprite, pos) in query.iter_mut() {
-        let index = match wfc.cells.get(&pos).unwrap().chosen_index {
-            Some(0) => DIRT_SPRITE_INDEX,
-            Some(1) => GRASS_SPRITE_INDEX,
-            Some(2) => {
-                let mut tile = tower.get_tile_mut("level1", "map1", pos).unwrap();
-                tile.transparent = false;
-                WATER_SPRITE_INDEX
+    let events = match wfc.next() {
+        Some(Ok(events)) => events,
+        result => {
+            if let Some(Err(e)) = result {
+                error!("could not generate map: {}", e);
             }
-            _ => FLOOR_SPRITE_INDEX,
-        };
-        sprite.index = index;
+            commands.remove_resource::<WFC>();
+            commands.remove_resource::<Wave>();
+            return;
+        }
+    };
+    for event in events {
+        match event {
+            WFCEvent::Collapsed { pos, tile } => {
+                set_terrain(&mut tower, &mut query, wave.map, &pos, wave.terrains[tile])
+            }
+            WFCEvent::Restarted { .. } => {
+                for pos in wfc.cells.positions() {
+                    set_terrain(&mut tower, &mut query, wave.map, &pos, Terrain::Floor);
+                }
+            }
+            WFCEvent::Banned { .. } => {}
+        }
     }
 }
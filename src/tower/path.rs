@@ -0,0 +1,166 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use bevy::prelude::*;
+
+use crate::position::{Position, DIRECTIONS};
+
+use super::{grid::Grid, tile::Tile};
+
+fn passable(grid: &Grid<Tile>, pos: &Position, is_blocker: &impl Fn(Entity) -> bool) -> bool {
+    match grid.get(pos) {
+        Some(tile) => tile.walkable() && !tile.entities.iter().any(|&e| is_blocker(e)),
+        None => false,
+    }
+}
+
+fn step_cost(grid: &Grid<Tile>, pos: &Position) -> u32 {
+    grid.get(pos)
+        .map_or(1, |tile| tile.terrain.movement_cost().max(1))
+}
+
+fn manhattan(a: &Position, b: &Position) -> u32 {
+    ((a.x - b.x).abs() + (a.y - b.y).abs()) as u32
+}
+
+pub fn a_star(
+    grid: &Grid<Tile>,
+    from: Position,
+    to: Position,
+    is_blocker: impl Fn(Entity) -> bool,
+) -> Option<Vec<Position>> {
+    if grid.get(&from).is_none() || !grid.get(&to).is_some_and(|tile| tile.walkable()) {
+        return None;
+    }
+    let mut open: BinaryHeap<Reverse<(u32, i32, i32)>> = BinaryHeap::new();
+    let mut came_from: HashMap<Position, Position> = HashMap::new();
+    let mut costs: HashMap<Position, u32> = HashMap::new();
+    costs.insert(from, 0);
+    open.push(Reverse((manhattan(&from, &to), from.x, from.y)));
+    while let Some(Reverse((_, x, y))) = open.pop() {
+        let current = Position::new(x, y);
+        if current == to {
+            let mut path: Vec<Position> = Vec::new();
+            let mut pos = current;
+            while pos != from {
+                path.push(pos);
+                pos = came_from[&pos];
+            }
+            path.reverse();
+            return Some(path);
+        }
+        let cost = costs[&current];
+        for dir in DIRECTIONS {
+            let next = current + *dir;
+            if next != to && !passable(grid, &next, &is_blocker) {
+                continue;
+            }
+            let next_cost = cost + step_cost(grid, &next);
+            if costs.get(&next).is_some_and(|&c| c <= next_cost) {
+                continue;
+            }
+            costs.insert(next, next_cost);
+            came_from.insert(next, current);
+            open.push(Reverse((next_cost + manhattan(&next, &to), next.x, next.y)));
+        }
+    }
+    None
+}
+
+fn tile_cost(tile: &Tile, is_blocker: &impl Fn(Entity) -> bool) -> Option<f32> {
+    if !tile.walkable() || tile.entities.iter().any(|&e| is_blocker(e)) {
+        return None;
+    }
+    Some(tile.terrain.movement_cost().max(1) as f32)
+}
+
+pub fn dijkstra(
+    grid: &Grid<Tile>,
+    sources: &[Position],
+    is_blocker: impl Fn(Entity) -> bool,
+) -> Grid<f32> {
+    grid.dijkstra_map(sources, |tile| tile_cost(tile, &is_blocker))
+}
+
+pub fn flee(
+    grid: &Grid<Tile>,
+    distances: &Grid<f32>,
+    factor: f32,
+    is_blocker: impl Fn(Entity) -> bool,
+) -> Grid<f32> {
+    distances.flee(grid, factor, |tile| tile_cost(tile, &is_blocker))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::tower::tile::Terrain;
+
+    fn grid_from_rows(rows: &[&str]) -> Grid<Tile> {
+        let height = rows.len();
+        let width = rows[0].len();
+        let mut grid: Grid<Tile> = Grid::new(width, height);
+        for (row, line) in rows.iter().rev().enumerate() {
+            for (col, c) in line.chars().enumerate() {
+                let terrain = match c {
+                    'W' => Terrain::Wall,
+                    '"' => Terrain::Grass,
+                    _ => Terrain::Floor,
+                };
+                let pos = Position::new(col as i32, row as i32);
+                grid.get_mut(&pos).unwrap().set_terrain(terrain);
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn test_a_star() {
+        let mut grid = grid_from_rows(&["...", ".\".", ".\".", ".\".", "..."]);
+        let from = Position::new(1, 0);
+        let to = Position::new(1, 4);
+        let path = a_star(&grid, from, to, |_| false).unwrap();
+        assert_eq!(path.len(), 6);
+        assert_eq!(path.last(), Some(&to));
+        assert!(path.iter().all(|pos| pos.x != 1 || pos.y == 4));
+
+        let blocker = Entity::from_raw(1);
+        for y in 1..=3 {
+            for x in [0, 2] {
+                let tile = grid.get_mut(&Position::new(x, y)).unwrap();
+                tile.entities.push(blocker);
+            }
+        }
+        let path = a_star(&grid, from, to, |e| e == blocker).unwrap();
+        assert_eq!(path.len(), 4);
+        assert_eq!(path.first(), Some(&Position::new(1, 1)));
+        assert!(a_star(&grid, from, Position::new(3, 0), |_| false).is_none());
+    }
+
+    #[test]
+    fn test_dijkstra() {
+        let grid = grid_from_rows(&["...", ".W.", "..."]);
+        let sources = [Position::new(0, 0), Position::new(2, 2)];
+        let distances = dijkstra(&grid, &sources, |_| false);
+        assert_eq!(distances.get(&Position::new(0, 0)), Some(&0.));
+        assert_eq!(distances.get(&Position::new(2, 0)), Some(&2.));
+        assert_eq!(distances.get(&Position::new(1, 1)), Some(&f32::INFINITY));
+        assert_eq!(distances.get(&Position::new(0, 2)), Some(&2.));
+        assert!(distances.downhill(&Position::new(0, 2)).is_some());
+        assert_eq!(distances.downhill(&Position::new(0, 0)), None);
+    }
+
+    #[test]
+    fn test_flee() {
+        let grid = grid_from_rows(&["....."]);
+        let distances = dijkstra(&grid, &[Position::new(0, 0)], |_| false);
+        let flee = flee(&grid, &distances, 1.2, |_| false);
+        assert_eq!(
+            flee.downhill(&Position::new(2, 0)),
+            Some(Position::new(3, 0))
+        );
+        assert_eq!(flee.downhill(&Position::new(4, 0)), None);
+    }
+}
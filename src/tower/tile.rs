@@ -1,18 +1,176 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{
+    DIRT_SPRITE_INDEX, DOOR_SPRITE_INDEX, FLOOR_SPRITE_INDEX, GRASS_SPRITE_INDEX,
+    STAIRS_DOWN_SPRITE_INDEX, STAIRS_UP_SPRITE_INDEX, WALL_SPRITE_INDEX, WATER_SPRITE_INDEX,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Stairs {
+    Up,
+    Down,
+}
+
+impl Stairs {
+    pub fn sprite_index(&self) -> usize {
+        match self {
+            Stairs::Up => STAIRS_UP_SPRITE_INDEX,
+            Stairs::Down => STAIRS_DOWN_SPRITE_INDEX,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Terrain {
+    Floor,
+    Wall,
+    Water,
+    Grass,
+    Door,
+    Dirt,
+}
 
 #[derive(Debug)]
-pub struct Tile {
+pub struct TerrainProperties {
+    pub name: &'static str,
     pub walkable: bool,
     pub transparent: bool,
+    pub movement_cost: u32,
+    pub sprite_index: usize,
+}
+
+impl Terrain {
+    pub fn properties(&self) -> &'static TerrainProperties {
+        match self {
+            Terrain::Floor => &TerrainProperties {
+                name: "floor",
+                walkable: true,
+                transparent: true,
+                movement_cost: 1,
+                sprite_index: FLOOR_SPRITE_INDEX,
+            },
+            Terrain::Wall => &TerrainProperties {
+                name: "wall",
+                walkable: false,
+                transparent: false,
+                movement_cost: 0,
+                sprite_index: WALL_SPRITE_INDEX,
+            },
+            Terrain::Water => &TerrainProperties {
+                name: "water",
+                walkable: false,
+                transparent: true,
+                movement_cost: 0,
+                sprite_index: WATER_SPRITE_INDEX,
+            },
+            Terrain::Grass => &TerrainProperties {
+                name: "grass",
+                walkable: true,
+                transparent: true,
+                movement_cost: 2,
+                sprite_index: GRASS_SPRITE_INDEX,
+            },
+            Terrain::Door => &TerrainProperties {
+                name: "door",
+                walkable: true,
+                transparent: false,
+                movement_cost: 1,
+                sprite_index: DOOR_SPRITE_INDEX,
+            },
+            Terrain::Dirt => &TerrainProperties {
+                name: "dirt",
+                walkable: true,
+                transparent: true,
+                movement_cost: 1,
+                sprite_index: DIRT_SPRITE_INDEX,
+            },
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        self.properties().name
+    }
+    pub fn walkable(&self) -> bool {
+        self.properties().walkable
+    }
+    pub fn transparent(&self) -> bool {
+        self.properties().transparent
+    }
+    pub fn movement_cost(&self) -> u32 {
+        self.properties().movement_cost
+    }
+    pub fn sprite_index(&self) -> usize {
+        self.properties().sprite_index
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Tile {
+    pub terrain: Terrain,
+    pub stairs: Option<Stairs>,
+    #[serde(skip)]
     pub entities: Vec<Entity>,
 }
 
+impl Tile {
+    pub fn new(terrain: Terrain) -> Self {
+        let mut tile = Tile::default();
+        tile.set_terrain(terrain);
+        tile
+    }
+    pub fn set_terrain(&mut self, terrain: Terrain) {
+        self.terrain = terrain;
+    }
+    pub fn walkable(&self) -> bool {
+        self.terrain.walkable()
+    }
+    pub fn transparent(&self) -> bool {
+        self.terrain.transparent()
+    }
+    pub fn sprite_index(&self) -> usize {
+        match self.stairs {
+            Some(stairs) => stairs.sprite_index(),
+            None => self.terrain.sprite_index(),
+        }
+    }
+}
+
 impl Default for Tile {
     fn default() -> Self {
         Tile {
-            walkable: true,
-            transparent: true,
+            terrain: Terrain::Floor,
+            stairs: None,
             entities: Vec::new(),
         }
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terrain_properties() {
+        let terrains = [
+            Terrain::Floor,
+            Terrain::Wall,
+            Terrain::Water,
+            Terrain::Grass,
+            Terrain::Door,
+            Terrain::Dirt,
+        ];
+        for terrain in terrains {
+            let name = format!("{:?}", terrain).to_lowercase();
+            assert_eq!(terrain.name(), name);
+        }
+        let mut tile = Tile::new(Terrain::Water);
+        assert!(!tile.walkable());
+        assert!(tile.transparent());
+        assert_eq!(tile.sprite_index(), WATER_SPRITE_INDEX);
+        tile.stairs = Some(Stairs::Up);
+        assert_eq!(tile.sprite_index(), STAIRS_UP_SPRITE_INDEX);
+        tile.set_terrain(Terrain::Door);
+        assert!(tile.walkable());
+        assert!(!tile.transparent());
+    }
+}
@@ -1,113 +1,351 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::position::{Position, DIRECTIONS};
 
-use super::{grid::Grid, tile::Tile};
+use super::{
+    grid::Grid,
+    legend::{Glyph, Legend, Spawn},
+    path,
+    tile::{Stairs, Terrain, Tile},
+};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub struct LevelId(usize);
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub struct MapId {
+    pub level: LevelId,
+    id: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub struct Location {
+    pub map: MapId,
+    pub pos: Position,
+}
 
+impl Location {
+    pub fn new(map: MapId, pos: Position) -> Self {
+        Location { map, pos }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Tower {
-    levels: HashMap<&'static str, Level>,
+    levels: HashMap<LevelId, Level>,
+    stairs: HashMap<Location, Location>,
+    next_id: usize,
 }
 
 impl Tower {
     pub fn new() -> Self {
-        let levels: HashMap<&str, Level> = HashMap::new();
-        Tower { levels }
+        let levels: HashMap<LevelId, Level> = HashMap::new();
+        let stairs: HashMap<Location, Location> = HashMap::new();
+        Tower {
+            levels,
+            stairs,
+            next_id: 0,
+        }
+    }
+    pub fn get_tile(&self, map: MapId, pos: &Position) -> Option<&Tile> {
+        self.get_map(map).and_then(|map| map.grid.get(pos))
+    }
+    pub fn get_tile_mut(&mut self, map: MapId, pos: &Position) -> Option<&mut Tile> {
+        self.get_map_mut(map).and_then(|map| map.grid.get_mut(pos))
     }
-    pub fn get_tile(&self, level: &str, map: &str, pos: &Position) -> Option<&Tile> {
-        if let Some(level) = self.levels.get(level) {
-            if let Some(map) = level.maps.get(map) {
-                return map.grid.get(pos);
+    pub fn is_walkable(&self, map: MapId, pos: &Position) -> bool {
+        self.get_tile(map, pos).is_some_and(|tile| tile.walkable())
+    }
+    pub fn insert_entity(&mut self, entity: Entity, location: &Location) -> bool {
+        match self.get_tile_mut(location.map, &location.pos) {
+            Some(tile) => {
+                if !tile.entities.contains(&entity) {
+                    tile.entities.push(entity);
+                }
+                true
             }
+            None => false,
         }
-        None
     }
-    pub fn get_tile_mut(&mut self, level: &str, map: &str, pos: &Position) -> Option<&mut Tile> {
-        if let Some(level) = self.levels.get_mut(level) {
-            if let Some(map) = level.maps.get_mut(map) {
-                return map.grid.get_mut(pos);
-            }
+    pub fn remove_entity(&mut self, entity: Entity, location: &Location) {
+        if let Some(tile) = self.get_tile_mut(location.map, &location.pos) {
+            tile.entities.retain(|&e| e != entity);
         }
-        None
+    }
+    pub fn move_entity(&mut self, entity: Entity, from: &Location, to: &Location) -> bool {
+        if self.get_tile(to.map, &to.pos).is_none() {
+            return false;
+        }
+        self.remove_entity(entity, from);
+        self.insert_entity(entity, to)
+    }
+    pub fn get_dimensions(&self, map: MapId) -> Option<(usize, usize)> {
+        self.get_map(map)
+            .map(|map| (map.grid.width, map.grid.height))
+    }
+    pub fn levels(&self) -> impl Iterator<Item = LevelId> + '_ {
+        self.levels.keys().copied()
+    }
+    pub fn maps(&self, level: LevelId) -> impl Iterator<Item = MapId> + '_ {
+        self.levels
+            .get(&level)
+            .into_iter()
+            .flat_map(|level| level.maps.keys().copied())
+    }
+    pub fn find_level(&self, name: &str) -> Option<LevelId> {
+        self.levels
+            .iter()
+            .find(|(_, level)| level.name == name)
+            .map(|(&id, _)| id)
+    }
+    pub fn find_map(&self, level: LevelId, name: &str) -> Option<MapId> {
+        self.levels.get(&level).and_then(|level| {
+            level
+                .maps
+                .iter()
+                .find(|(_, map)| map.name == name)
+                .map(|(&id, _)| id)
+        })
+    }
+    pub fn level_name(&self, level: LevelId) -> Option<&str> {
+        self.levels.get(&level).map(|level| level.name.as_str())
+    }
+    pub fn map_name(&self, map: MapId) -> Option<&str> {
+        self.get_map(map).map(|map| map.name.as_str())
+    }
+    pub fn insert_level(&mut self, name: impl Into<String>) -> LevelId {
+        let id = LevelId(self.next_id());
+        self.levels.insert(
+            id,
+            Level {
+                name: name.into(),
+                maps: HashMap::new(),
+            },
+        );
+        id
     }
     pub fn insert_empty_map(
         &mut self,
-        level_name: &'static str,
-        map_name: &'static str,
+        level: LevelId,
+        name: impl Into<String>,
         width: usize,
         height: usize,
-    ) {
-        if let Some(level) = self.levels.get_mut(level_name) {
-            level
-                .maps
-                .insert(map_name, Map::new(map_name, width, height));
-        } else {
-            let mut maps: HashMap<&'static str, Map> = HashMap::new();
-            maps.insert(map_name, Map::new(map_name, width, height));
-            self.levels.insert(
-                level_name,
-                Level {
-                    name: level_name,
-                    maps,
-                },
-            );
-        }
+    ) -> Option<MapId> {
+        self.insert_map(level, Map::new(name, width, height))
     }
     pub fn insert_map_from_str(
         &mut self,
-        level_name: &'static str,
-        map_name: &'static str,
+        level: LevelId,
+        name: impl Into<String>,
         width: usize,
         height: usize,
         s: &str,
-    ) {
-        if let Some(level) = self.levels.get_mut(level_name) {
-            level
-                .maps
-                .insert(map_name, Map::from_str(map_name, width, height, s));
-        } else {
-            let mut maps: HashMap<&'static str, Map> = HashMap::new();
-            maps.insert(map_name, Map::from_str(map_name, width, height, s));
-            self.levels.insert(
-                level_name,
-                Level {
-                    name: level_name,
-                    maps,
-                },
-            );
-        }
-    }
-    pub fn get_visible(
-        &self,
-        level_name: &'static str,
-        map_name: &'static str,
-        origin: &Position,
-    ) -> HashSet<Position> {
-        if let Some(level) = self.levels.get(level_name) {
-            if let Some(map) = level.maps.get(map_name) {
-                return map.get_visible(origin);
+    ) -> Option<MapId> {
+        self.insert_map(level, Map::from_str(name, width, height, s))
+    }
+    pub fn insert_map_from_grid(
+        &mut self,
+        level: LevelId,
+        name: impl Into<String>,
+        grid: Grid<Tile>,
+    ) -> Option<MapId> {
+        self.insert_map(level, Map::from_grid(name, grid))
+    }
+    pub fn insert_map_from_legend(
+        &mut self,
+        level: LevelId,
+        name: impl Into<String>,
+        width: usize,
+        height: usize,
+        s: &str,
+        legend: &Legend,
+    ) -> Option<MapId> {
+        self.insert_map(level, Map::from_legend(name, width, height, s, legend))
+    }
+    pub fn map_to_string(&self, map: MapId, legend: &Legend) -> Option<String> {
+        self.get_map(map).map(|map| map.to_string_with(legend))
+    }
+    pub fn get_spawns(&self, map: MapId) -> Vec<(Position, Spawn)> {
+        self.get_map(map)
+            .map(|map| {
+                map.spawns
+                    .iter()
+                    .map(|(&pos, &spawn)| (pos, spawn))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+    pub fn find_stairs(&self, map: MapId, stairs: Stairs) -> Vec<Position> {
+        self.get_map(map)
+            .map(|map| {
+                map.grid
+                    .positions()
+                    .filter(|pos| map.grid.get(pos).unwrap().stairs == Some(stairs))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+    pub fn remove_level(&mut self, level: LevelId) -> bool {
+        let maps: Vec<MapId> = self.maps(level).collect();
+        for map in maps {
+            self.remove_map(map);
+        }
+        self.levels.remove(&level).is_some()
+    }
+    pub fn remove_map(&mut self, map: MapId) -> bool {
+        let removed = self
+            .levels
+            .get_mut(&map.level)
+            .and_then(|level| level.maps.remove(&map))
+            .is_some();
+        let links: Vec<(Location, Location)> = self
+            .stairs
+            .iter()
+            .filter(|(from, _)| from.map == map)
+            .map(|(&from, &to)| (from, to))
+            .collect();
+        for (from, to) in links {
+            self.stairs.remove(&from);
+            self.stairs.remove(&to);
+            if let Some(tile) = self.get_tile_mut(to.map, &to.pos) {
+                tile.stairs = None;
             }
         }
+        removed
+    }
+    pub fn get_visible(&self, map: MapId, origin: &Position) -> HashSet<Position> {
+        if let Some(map) = self.get_map(map) {
+            return map.get_visible(origin);
+        }
         HashSet::new()
     }
+    pub fn find_path(
+        &self,
+        map: MapId,
+        from: Position,
+        to: Position,
+        is_blocker: impl Fn(Entity) -> bool,
+    ) -> Option<Vec<Position>> {
+        self.get_map(map)
+            .and_then(|map| path::a_star(&map.grid, from, to, is_blocker))
+    }
+    pub fn distance_map(
+        &self,
+        map: MapId,
+        sources: &[Position],
+        is_blocker: impl Fn(Entity) -> bool,
+    ) -> Option<Grid<f32>> {
+        self.get_map(map)
+            .map(|map| path::dijkstra(&map.grid, sources, is_blocker))
+    }
+    pub fn flee_map(
+        &self,
+        map: MapId,
+        threats: &[Position],
+        factor: f32,
+        is_blocker: impl Fn(Entity) -> bool,
+    ) -> Option<Grid<f32>> {
+        let distances = self.distance_map(map, threats, &is_blocker)?;
+        let map = self.get_map(map)?;
+        Some(path::flee(&map.grid, &distances, factor, is_blocker))
+    }
+    pub fn insert_stairs(&mut self, lower: Location, upper: Location) -> bool {
+        if self.get_tile(lower.map, &lower.pos).is_none()
+            || self.get_tile(upper.map, &upper.pos).is_none()
+        {
+            return false;
+        }
+        if let Some(tile) = self.get_tile_mut(lower.map, &lower.pos) {
+            tile.stairs = Some(Stairs::Up);
+        }
+        if let Some(tile) = self.get_tile_mut(upper.map, &upper.pos) {
+            tile.stairs = Some(Stairs::Down);
+        }
+        self.stairs.insert(lower, upper);
+        self.stairs.insert(upper, lower);
+        true
+    }
+    pub fn get_stairs_destination(&self, from: &Location) -> Option<Location> {
+        self.stairs.get(from).copied()
+    }
+    fn insert_map(&mut self, level: LevelId, map: Map) -> Option<MapId> {
+        let id = MapId {
+            level,
+            id: self.next_id(),
+        };
+        let level = self.levels.get_mut(&level)?;
+        level.maps.insert(id, map);
+        Some(id)
+    }
+    fn get_map(&self, map: MapId) -> Option<&Map> {
+        self.levels
+            .get(&map.level)
+            .and_then(|level| level.maps.get(&map))
+    }
+    fn get_map_mut(&mut self, map: MapId) -> Option<&mut Map> {
+        self.levels
+            .get_mut(&map.level)
+            .and_then(|level| level.maps.get_mut(&map))
+    }
+    fn next_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+pub fn terrain_from_str(width: usize, height: usize, s: &str) -> Grid<Terrain> {
+    Map::from_str("sample", width, height, s)
+        .grid
+        .map(|tile| tile.terrain)
 }
 
+#[derive(Serialize, Deserialize)]
 struct Level {
-    name: &'static str,
-    maps: HashMap<&'static str, Map>,
+    name: String,
+    maps: HashMap<MapId, Map>,
 }
 
+#[derive(Serialize, Deserialize)]
 struct Map {
-    name: &'static str,
+    name: String,
     grid: Grid<Tile>,
+    spawns: HashMap<Position, Spawn>,
 }
 
 impl Map {
-    fn new(name: &'static str, width: usize, height: usize) -> Self {
+    fn new(name: impl Into<String>, width: usize, height: usize) -> Self {
         let grid: Grid<Tile> = Grid::new(width, height);
-        Map { name, grid }
+        Map {
+            name: name.into(),
+            grid,
+            spawns: HashMap::new(),
+        }
     }
-    fn from_str(name: &'static str, width: usize, height: usize, s: &str) -> Self {
+    fn from_grid(name: impl Into<String>, grid: Grid<Tile>) -> Self {
+        Map {
+            name: name.into(),
+            grid,
+            spawns: HashMap::new(),
+        }
+    }
+    fn from_str(name: impl Into<String>, width: usize, height: usize, s: &str) -> Self {
+        Map::from_legend(name, width, height, s, &Legend::default())
+    }
+    fn from_legend(
+        name: impl Into<String>,
+        width: usize,
+        height: usize,
+        s: &str,
+        legend: &Legend,
+    ) -> Self {
         let mut grid: Grid<Tile> = Grid::new(width, height);
+        let mut spawns: HashMap<Position, Spawn> = HashMap::new();
         let mut y = 0;
         for row in s.lines().map(str::trim).rev() {
             if !row.starts_with("|") {
@@ -117,22 +355,46 @@ impl Map {
             let splits: Vec<&str> = row.trim_start_matches("|").split_terminator("|").collect();
             for split in splits {
                 let pos = Position { x, y };
-                let chars: Vec<char> = split.chars().collect();
-                match chars[0] {
-                    'W' => {
-                        if let Some(tile) = grid.get_mut(&pos) {
-                            tile.walkable = false;
-                            tile.transparent = false;
+                let c = split.chars().next().unwrap_or(' ');
+                if let Some(tile) = grid.get_mut(&pos) {
+                    match legend.glyph(c) {
+                        Some(Glyph::Terrain(terrain)) => tile.set_terrain(terrain),
+                        Some(Glyph::Stairs(stairs)) => tile.stairs = Some(stairs),
+                        Some(Glyph::Spawn(spawn)) => {
+                            spawns.insert(pos, spawn);
                         }
+                        None => {}
                     }
-                    _ => {}
                 }
                 x += 1;
             }
             y += 1;
         }
 
-        Map { name, grid }
+        Map {
+            name: name.into(),
+            grid,
+            spawns,
+        }
+    }
+    fn to_string_with(&self, legend: &Legend) -> String {
+        let mut s = String::new();
+        for y in (0..self.grid.height as i32).rev() {
+            s.push('|');
+            for x in 0..self.grid.width as i32 {
+                let pos = Position::new(x, y);
+                let tile = self.grid.get(&pos).unwrap();
+                let glyph = match (self.spawns.get(&pos), tile.stairs) {
+                    (Some(&spawn), _) => Glyph::Spawn(spawn),
+                    (None, Some(stairs)) => Glyph::Stairs(stairs),
+                    (None, None) => Glyph::Terrain(tile.terrain),
+                };
+                s.push(legend.char(glyph).unwrap_or('?'));
+                s.push('|');
+            }
+            s.push('\n');
+        }
+        s
     }
     fn get_visible(&self, origin: &Position) -> HashSet<Position> {
         let mut visible: HashSet<Position> = HashSet::new();
@@ -152,14 +414,14 @@ impl Map {
                 continue;
             }
             let tile = tile_option.unwrap();
-            if !tile.transparent || row.symmetric(&relative_position) {
+            if !tile.transparent() || row.symmetric(&relative_position) {
                 vis.insert(absolute_pos);
             }
             if let Some(prev_tile) = prev {
-                if !prev_tile.transparent && tile.transparent {
+                if !prev_tile.transparent() && tile.transparent() {
                     row.start = relative_position.slope();
                 }
-                if prev_tile.transparent && !tile.transparent {
+                if prev_tile.transparent() && !tile.transparent() {
                     let mut next_row = row.next();
                     next_row.end = relative_position.slope();
                     self.scan(next_row, vis, origin, dir);
@@ -168,13 +430,19 @@ impl Map {
             prev = Some(tile);
         }
         if let Some(prev_tile) = prev {
-            if prev_tile.transparent {
+            if prev_tile.transparent() {
                 self.scan(row.next(), vis, origin, dir);
             }
         }
     }
 }
 
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_string_with(&Legend::default()))
+    }
+}
+
 struct Row {
     depth: f32,
     start: f32,
@@ -225,9 +493,99 @@ pub mod tests {
         | | | | | |
             ",
         );
-        assert!(!map.grid.get(&Position { x: 1, y: 2 }).unwrap().walkable);
-        assert!(map.grid.get(&Position { x: 2, y: 2 }).unwrap().walkable);
-        assert!(!map.grid.get(&Position { x: 3, y: 1 }).unwrap().walkable);
+        assert!(!map.grid.get(&Position { x: 1, y: 2 }).unwrap().walkable());
+        assert!(map.grid.get(&Position { x: 2, y: 2 }).unwrap().walkable());
+        assert!(!map.grid.get(&Position { x: 3, y: 1 }).unwrap().walkable());
+    }
+
+    #[test]
+    fn test_map_from_str_legend() {
+        let map = Map::from_str(
+            "test_map",
+            5,
+            3,
+            "
+        |W|~|\"|+|<|
+        |.|@| |g|>|
+        |W|W|W|W|W|
+            ",
+        );
+        let tile = |x, y| map.grid.get(&Position::new(x, y)).unwrap();
+        assert_eq!(tile(0, 2).terrain, Terrain::Wall);
+        assert_eq!(tile(1, 2).terrain, Terrain::Water);
+        assert!(!tile(1, 2).walkable());
+        assert_eq!(tile(2, 2).terrain, Terrain::Grass);
+        assert_eq!(tile(3, 2).terrain, Terrain::Door);
+        assert!(tile(3, 2).walkable());
+        assert!(!tile(3, 2).transparent());
+        assert_eq!(tile(4, 2).stairs, Some(Stairs::Up));
+        assert_eq!(tile(0, 1).terrain, Terrain::Floor);
+        assert_eq!(tile(4, 1).stairs, Some(Stairs::Down));
+        assert_eq!(map.spawns.get(&Position::new(1, 1)), Some(&Spawn::Player));
+        assert_eq!(
+            map.spawns.get(&Position::new(3, 1)),
+            Some(&Spawn::Hostile('g'))
+        );
+    }
+
+    #[test]
+    fn test_map_to_string_round_trip() {
+        let s = "|W|~|\"|+|<|\n| |@| |g|>|\n|W|W|W|W|W|\n";
+        let map = Map::from_str("test_map", 5, 3, s);
+        assert_eq!(map.to_string(), s);
+
+        let mut legend = Legend::new();
+        legend.insert('#', Glyph::Terrain(Terrain::Wall));
+        legend.insert('.', Glyph::Terrain(Terrain::Floor));
+        legend.insert('&', Glyph::Spawn(Spawn::Hostile('g')));
+        let mut tower = Tower::new();
+        let level = tower.insert_level("level1");
+        let map = tower
+            .insert_map_from_legend(level, "map1", 3, 1, "|#|.|&|", &legend)
+            .unwrap();
+        assert!(!tower.is_walkable(map, &Position::ZERO));
+        assert_eq!(
+            tower.get_spawns(map),
+            vec![(Position::new(2, 0), Spawn::Hostile('g'))]
+        );
+        assert_eq!(
+            tower.map_to_string(map, &Legend::default()),
+            Some("|W| |g|\n".to_string())
+        );
+        assert_eq!(
+            tower.map_to_string(map, &legend),
+            Some("|#|.|&|\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_find_stairs_and_spawns() {
+        let mut tower = Tower::new();
+        let level = tower.insert_level("level1");
+        let map = tower
+            .insert_map_from_str(level, "map1", 3, 2, "|<|@|a|\n| | |>|")
+            .unwrap();
+        assert_eq!(
+            tower.find_stairs(map, Stairs::Up),
+            vec![Position::new(0, 1)]
+        );
+        assert_eq!(
+            tower.find_stairs(map, Stairs::Down),
+            vec![Position::new(2, 0)]
+        );
+        let mut spawns = tower.get_spawns(map);
+        spawns.sort_by_key(|(pos, _)| pos.x);
+        assert_eq!(
+            spawns,
+            vec![
+                (Position::new(1, 1), Spawn::Player),
+                (Position::new(2, 1), Spawn::Hostile('a')),
+            ]
+        );
+        assert_eq!(
+            tower.map_to_string(map, &Legend::default()),
+            Some("|<|@|a|\n| | |>|\n".to_string())
+        );
     }
 
     #[test]
@@ -247,4 +605,95 @@ pub mod tests {
         let visible = map.get_visible(&Position::new(2, 2));
         assert_eq!(visible.len(), 12);
     }
+
+    #[test]
+    fn test_stairs() {
+        let mut tower = Tower::new();
+        let level1 = tower.insert_level("level1");
+        let level2 = tower.insert_level("level2");
+        let map1 = tower.insert_empty_map(level1, "map1", 5, 5).unwrap();
+        let map2 = tower.insert_empty_map(level2, "map1", 5, 5).unwrap();
+        let lower = Location::new(map1, Position::new(4, 4));
+        let upper = Location::new(map2, Position::new(0, 0));
+        assert!(tower.insert_stairs(lower, upper));
+        assert_eq!(tower.get_stairs_destination(&lower), Some(upper));
+        assert_eq!(tower.get_stairs_destination(&upper), Some(lower));
+        assert_eq!(
+            tower.get_tile(map1, &lower.pos).unwrap().stairs,
+            Some(Stairs::Up)
+        );
+        assert_eq!(
+            tower.get_tile(map2, &upper.pos).unwrap().stairs,
+            Some(Stairs::Down)
+        );
+        let outside = Location::new(map2, Position::new(5, 5));
+        assert!(!tower.insert_stairs(lower, outside));
+        assert!(tower
+            .get_stairs_destination(&Location::new(map1, Position::ZERO))
+            .is_none());
+    }
+
+    #[test]
+    fn test_move_entity() {
+        let mut tower = Tower::new();
+        let level = tower.insert_level("level1");
+        let map = tower
+            .insert_map_from_str(level, "map1", 3, 1, "| |W| |")
+            .unwrap();
+        assert!(tower.is_walkable(map, &Position::new(0, 0)));
+        assert!(!tower.is_walkable(map, &Position::new(1, 0)));
+        assert!(!tower.is_walkable(map, &Position::new(3, 0)));
+
+        let entity = Entity::from_raw(1);
+        let from = Location::new(map, Position::new(0, 0));
+        let to = Location::new(map, Position::new(2, 0));
+        assert!(tower.insert_entity(entity, &from));
+        assert!(tower.insert_entity(entity, &from));
+        assert_eq!(
+            tower.get_tile(map, &from.pos).unwrap().entities,
+            vec![entity]
+        );
+        assert!(tower.move_entity(entity, &from, &to));
+        assert!(tower.get_tile(map, &from.pos).unwrap().entities.is_empty());
+        assert_eq!(tower.get_tile(map, &to.pos).unwrap().entities, vec![entity]);
+        let outside = Location::new(map, Position::new(0, 1));
+        assert!(!tower.move_entity(entity, &to, &outside));
+        assert_eq!(tower.get_tile(map, &to.pos).unwrap().entities, vec![entity]);
+    }
+
+    #[test]
+    fn test_level_and_map_ids() {
+        let mut tower = Tower::new();
+        let floors: Vec<LevelId> = (1..=3)
+            .map(|i| tower.insert_level(format!("floor-{i}")))
+            .collect();
+        let maps: Vec<MapId> = floors
+            .iter()
+            .map(|&level| tower.insert_empty_map(level, "main", 5, 5).unwrap())
+            .collect();
+        assert_eq!(tower.levels().count(), 3);
+        assert_eq!(tower.find_level("floor-2"), Some(floors[1]));
+        assert_eq!(tower.find_map(floors[1], "main"), Some(maps[1]));
+        assert_eq!(tower.level_name(floors[2]), Some("floor-3"));
+        assert_eq!(tower.map_name(maps[0]), Some("main"));
+        assert!(tower.insert_stairs(
+            Location::new(maps[0], Position::new(1, 1)),
+            Location::new(maps[1], Position::new(1, 1)),
+        ));
+
+        assert!(tower.remove_level(floors[1]));
+        assert!(!tower.remove_level(floors[1]));
+        assert!(tower.find_level("floor-2").is_none());
+        assert!(tower.get_tile(maps[1], &Position::ZERO).is_none());
+        assert_eq!(tower.maps(floors[1]).count(), 0);
+        assert!(tower
+            .get_stairs_destination(&Location::new(maps[0], Position::new(1, 1)))
+            .is_none());
+        assert!(tower
+            .get_tile(maps[0], &Position::new(1, 1))
+            .unwrap()
+            .stairs
+            .is_none());
+        assert!(tower.insert_empty_map(floors[1], "main", 5, 5).is_none());
+    }
 }
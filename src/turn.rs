@@ -0,0 +1,176 @@
+use std::ops::DerefMut;
+
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+
+use crate::{constants::ACTION_COST, player::Player, tower::GameState};
+
+pub struct TurnPlugin;
+
+impl Plugin for TurnPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TurnState::World)
+            .insert_resource(TurnClock { ticks: 0 })
+            .add_stage_after(
+                CoreStage::Update,
+                TurnStage,
+                SystemStage::parallel().with_run_criteria(run_if_world_turn),
+            )
+            .add_system_to_stage(CoreStage::PreUpdate, update_turn_state_system)
+            .add_system_to_stage(TurnStage, grant_energy_system.label(TurnSystem::Energy))
+            .add_system_to_stage(TurnStage, end_turn_system.label(TurnSystem::End));
+    }
+}
+
+#[derive(StageLabel)]
+pub struct TurnStage;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TurnSystem {
+    Energy,
+    Ai,
+    End,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurnState {
+    Player,
+    World,
+}
+
+pub struct TurnClock {
+    pub ticks: u64,
+}
+
+#[derive(Debug, Component)]
+pub struct Actor {
+    pub speed: i32,
+    pub energy: i32,
+    pub ready: bool,
+}
+
+impl Actor {
+    pub fn new(speed: i32) -> Self {
+        Actor {
+            speed,
+            energy: 0,
+            ready: false,
+        }
+    }
+    pub fn can_act(&self) -> bool {
+        self.energy >= ACTION_COST
+    }
+    pub fn spend(&mut self, cost: i32) {
+        self.energy -= cost;
+        self.ready = false;
+    }
+    fn ticks_until_ready(&self) -> i32 {
+        if self.can_act() || self.speed <= 0 {
+            return 0;
+        }
+        (ACTION_COST - self.energy + self.speed - 1) / self.speed
+    }
+}
+
+pub fn run_if_player_turn(state: Res<TurnState>, game_state: Res<GameState>) -> ShouldRun {
+    match (*state, *game_state) {
+        (TurnState::Player, GameState::Playing) => ShouldRun::Yes,
+        _ => ShouldRun::No,
+    }
+}
+
+pub fn run_if_world_turn(state: Res<TurnState>, game_state: Res<GameState>) -> ShouldRun {
+    match (*state, *game_state) {
+        (TurnState::World, GameState::Playing) => ShouldRun::Yes,
+        _ => ShouldRun::No,
+    }
+}
+
+fn next_turn_state<'a>(actors: impl Iterator<Item = (&'a Actor, bool)>) -> TurnState {
+    let mut player_ready = false;
+    for (actor, is_player) in actors {
+        if !actor.can_act() {
+            continue;
+        }
+        if !is_player {
+            return TurnState::World;
+        }
+        player_ready = true;
+    }
+    match player_ready {
+        true => TurnState::Player,
+        false => TurnState::World,
+    }
+}
+
+fn grant_energy<A: DerefMut<Target = Actor>>(actors: impl Iterator<Item = A>) -> u64 {
+    let mut actors: Vec<A> = actors.filter(|actor| actor.speed > 0).collect();
+    let ticks = if actors.iter().any(|actor| actor.can_act()) {
+        0
+    } else {
+        actors
+            .iter()
+            .map(|actor| actor.ticks_until_ready())
+            .min()
+            .unwrap_or(0)
+    };
+    for actor in actors.iter_mut() {
+        actor.energy += actor.speed * ticks;
+        actor.ready = actor.can_act();
+    }
+    ticks as u64
+}
+
+fn update_turn_state_system(query: Query<(&Actor, Option<&Player>)>, mut state: ResMut<TurnState>) {
+    let next = next_turn_state(
+        query
+            .iter()
+            .map(|(actor, player)| (actor, player.is_some())),
+    );
+    if *state != next {
+        *state = next;
+    }
+}
+
+fn grant_energy_system(mut query: Query<&mut Actor>, mut clock: ResMut<TurnClock>) {
+    clock.ticks += grant_energy(query.iter_mut());
+}
+
+fn end_turn_system(mut query: Query<&mut Actor, Without<Player>>) {
+    for mut actor in query.iter_mut() {
+        if actor.ready {
+            actor.spend(ACTION_COST);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn test_energy_scheduling() {
+        let mut player = Actor::new(100);
+        let mut fast = Actor::new(200);
+        let mut slow = Actor::new(50);
+        let mut actions = [0, 0, 0];
+        let mut clock = 0;
+        while actions[0] < 4 {
+            let state =
+                next_turn_state([(&player, true), (&fast, false), (&slow, false)].into_iter());
+            if state == TurnState::Player {
+                player.spend(ACTION_COST);
+                actions[0] += 1;
+                continue;
+            }
+            clock += grant_energy([&mut player, &mut fast, &mut slow].into_iter());
+            for (i, actor) in [&mut fast, &mut slow].into_iter().enumerate() {
+                if actor.ready {
+                    actor.spend(ACTION_COST);
+                    actions[i + 1] += 1;
+                }
+            }
+        }
+        assert_eq!(clock, 4);
+        assert_eq!(actions, [4, 8, 2]);
+    }
+}
@@ -62,22 +62,23 @@ pub mod tests {
 
     #[test]
     fn test_raycast() {
-        let level_name = "test_level";
-        let map_name = "test_map";
         let mut tower = Tower::new();
-        tower.insert_map_from_str(
-            level_name,
-            map_name,
-            5,
-            5,
-            "
+        let level = tower.insert_level("test_level");
+        let map = tower
+            .insert_map_from_str(
+                level,
+                "test_map",
+                5,
+                5,
+                "
         | | | | | |
         | | | | | |
         | |W| | | |
         | | | |W| |
         | | | | | |
             ",
-        );
+            )
+            .unwrap();
         let tests: Vec<(Position, Position, bool)> = vec![
             // Horizontal Positive
             (Position::new(0, 0), Position::new(4, 0), true),
@@ -103,11 +104,7 @@ pub mod tests {
             let mut visible: HashSet<Position> = HashSet::new();
             for pos in rc {
                 visible.insert(pos);
-                if !tower
-                    .get_tile(level_name, map_name, &pos)
-                    .unwrap()
-                    .transparent
-                {
+                if !tower.get_tile(map, &pos).unwrap().transparent() {
                     break;
                 }
             }